@@ -0,0 +1,160 @@
+use std::hash::Hasher;
+use std::io;
+use std::fmt::Write;
+use digest::{Update, Reset, FixedOutputDirty, Output};
+use digest::consts::U16;
+
+pub const SUFFIX: [u8; 5] = [17, 31, 73, 47, 23];
+pub const ROUNDS: u32 = 64;
+
+// The original round: every swap indexes modulo the ring size. Kept to benchmark against.
+fn pinch(xs: &mut [u8], pos: usize, length: usize) {
+    let count = length / 2;
+    for offset in 0..count {
+        let i = (pos + offset) % xs.len();
+        let j = (pos + length - offset - 1) % xs.len();
+        xs.swap(i, j);
+    }
+}
+
+fn xor(xs: &[u8]) -> u8 {
+    let mut output = 0;
+    for x in xs {
+        output ^= x;
+    }
+    output
+}
+
+fn compact(xs: &[u8], block_size: usize) -> Vec<u8> {
+    xs.chunks(block_size).map(xor).collect()
+}
+
+fn initialize(length: usize) -> Vec<u8> {
+    let mut xs = Vec::with_capacity(length);
+    for i in 0..length {
+        xs.push(i as u8);
+    }
+    xs
+}
+
+pub fn hashify_pinched(input: &[u8], rounds: u32) -> Vec<u8> {
+    let mut skip = 0;
+    let mut pos = 0;
+    let mut sparse = initialize(256);
+    for _ in 0..rounds {
+        for x in input {
+            let length = *x as usize;
+            pinch(&mut sparse, pos, length);
+            pos += skip + length;
+            skip += 1;
+        }
+    }
+    compact(&sparse, 16)
+}
+
+// Reversals index the ring directly until one would run past its end; then the ring is rotated
+// so the current position is at index 0, which makes that reversal (and the next few) a plain
+// slice reverse. `origin` tracks where the real index 0 has ended up.
+// A batched mode, hashing several inputs in lockstep, was tried for day14's 128 rows and measured
+// slower than hashing them one at a time like this, so there isn't one; day14 hashes its rows on
+// separate threads instead.
+fn sparse_hash(input: &[u8], rounds: u32) -> [u8; 256] {
+    let mut ring = [0u8; 256];
+    for (i, x) in ring.iter_mut().enumerate() {
+        *x = i as u8;
+    }
+    let mut origin = 0;
+    let mut pos = 0;
+    let mut skip = 0;
+    for _ in 0..rounds {
+        for &length in input {
+            let length = length as usize;
+            if pos + length > 256 {
+                ring.rotate_left(pos);
+                origin = (origin + 256 - pos) % 256;
+                pos = 0;
+            }
+            ring[pos..pos + length].reverse();
+            pos = (pos + length + skip) % 256;
+            skip = (skip + 1) % 256;
+        }
+    }
+    ring.rotate_left(origin);
+    ring
+}
+
+pub fn hashify(input: &[u8], rounds: u32) -> Vec<u8> {
+    compact(&sparse_hash(input, rounds), 16)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(&mut hex, "{:02x}", byte).expect("Unable to write to output.");
+    }
+    hex
+}
+
+// The full knot hash: the input's bytes plus the standard suffix as lengths, 64 rounds, dense
+// output. Every round replays the whole input, so input is buffered until the hash is finalized.
+#[derive(Debug, Clone, Default)]
+pub struct KnotHasher {
+    input: Vec<u8>,
+}
+
+impl KnotHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hash(&self) -> [u8; 16] {
+        let mut lengths = Vec::with_capacity(self.input.len() + SUFFIX.len());
+        lengths.extend_from_slice(&self.input);
+        lengths.extend_from_slice(&SUFFIX);
+        let mut output = [0; 16];
+        output.copy_from_slice(&hashify(&lengths, ROUNDS));
+        output
+    }
+}
+
+// The first eight bytes of the hash, big-endian, so HashMap and friends can use it.
+impl Hasher for KnotHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.input.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash()[..8].iter().fold(0, |acc, b| (acc << 8) | *b as u64)
+    }
+}
+
+// Together these give KnotHasher the digest::Digest API: new, update, finalize and so on.
+impl Update for KnotHasher {
+    fn update(&mut self, data: impl AsRef<[u8]>) {
+        self.input.extend_from_slice(data.as_ref());
+    }
+}
+
+impl FixedOutputDirty for KnotHasher {
+    type OutputSize = U16;
+    fn finalize_into_dirty(&mut self, out: &mut Output<Self>) {
+        out.copy_from_slice(&self.hash());
+    }
+}
+
+impl Reset for KnotHasher {
+    fn reset(&mut self) {
+        self.input.clear();
+    }
+}
+
+impl io::Write for KnotHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.input.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,58 +1,71 @@
-use std::fmt::Write;
+extern crate digest;
 
-fn pinch(xs: &mut [u8], pos: usize, length: usize) {
-    let count = length / 2;
-    for offset in 0..count {
-        let i = (pos + offset) % xs.len();
-        let j = (pos + length - offset - 1) % xs.len();
-        xs.swap(i, j);
-    }
-}
-
-fn xor(xs: &[u8]) -> u8 {
-    let mut output = 0;
-    for x in xs {
-        output ^= x;
-    }
-    output
-}
+mod knot;
 
-fn compact(xs: &[u8], block_size: usize) -> Vec<u8> {
-    xs.chunks(block_size).map(xor).collect()
-}
+use std::env;
+use std::fs::File;
+use std::io;
+use std::process;
+use std::time::Instant;
+use digest::Digest;
+use knot::{KnotHasher, to_hex};
 
-fn initialize(length: usize) -> Vec<u8> {
-    let mut xs = Vec::with_capacity(length);
-    for i in 0..length {
-        xs.push(i as u8);
+// like sha256sum: one "hash  name" line per file, with "-" or no files at all meaning stdin
+fn sum(paths: &[String]) -> bool {
+    let stdin = [String::from("-")];
+    let paths = if paths.is_empty() { &stdin[..] } else { paths };
+    let mut ok = true;
+    for path in paths {
+        let mut hasher = KnotHasher::new();
+        let copied = if path == "-" {
+            io::copy(&mut io::stdin(), &mut hasher)
+        } else {
+            File::open(path).and_then(|mut f| io::copy(&mut f, &mut hasher))
+        };
+        match copied {
+            Ok(_) => println!("{}  {}", to_hex(&hasher.finalize()), path),
+            Err(e) => { eprintln!("{}: {}", path, e); ok = false; }
+        }
     }
-    xs
+    ok
 }
 
-fn hashify(input: &[u8], rounds: u32) -> Vec<u8> {
-    let mut skip = 0;
-    let mut pos = 0;
-    let mut sparse = initialize(256);
-    for _ in 0..rounds {
-        for x in input {
-            let length = *x as usize;
-            pinch(&mut sparse, pos, length);
-            pos += skip + length;
-            skip += 1;
+// day14 builds its grid from 128 knot hashes of "key-row"; time building it the old way and the new.
+// This is the benchmark for day14's grid build too: day14 uses the same sparse_hash but has no
+// pinch to compare it with.
+fn bench(grids: u32) {
+    let inputs: Vec<Vec<u8>> = (0..128).map(|n| {
+        let mut input: Vec<u8> = format!("flqrgnkx-{}", n).into();
+        input.extend_from_slice(&knot::SUFFIX);
+        input
+    }).collect();
+    let build = |hashify: fn(&[u8], u32) -> Vec<u8>| {
+        let start = Instant::now();
+        let mut rows = Vec::new();
+        for _ in 0..grids {
+            rows = inputs.iter().map(|i| hashify(i, knot::ROUNDS)).collect();
         }
-    }
-    compact(&mut sparse, 16)
+        (rows, start.elapsed().as_secs_f64())
+    };
+    let (pinched, pinched_time) = build(knot::hashify_pinched);
+    let (rotated, rotated_time) = build(knot::hashify);
+    assert_eq!(pinched, rotated, "Rotating origin disagrees with pinch!");
+    println!("Built the day14 grid {} times.", grids);
+    println!("Pinch: {:.3}s, rotating origin: {:.3}s ({:.1}x)", pinched_time, rotated_time, pinched_time / rotated_time);
 }
 
 fn main() {
-    let mut chars: Vec<u8> = include_str!("../input").trim().into();
-    let mut suffix = vec![17u8, 31u8, 73u8, 47u8, 23u8];
-    chars.append(&mut suffix);
-
-    let output = hashify(&chars, 64);
-    let mut hash = String::new();
-    for byte in output {
-        write!(&mut hash, "{:02x}", byte).expect("Unable to write to output.");
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|a| a.as_str()) == Some("--bench") {
+        return bench(args.get(1).map_or(100, |n| n.parse().expect("--bench needs a number.")));
     }
-    println!("Hash is: {}", hash);
+    if args.first().map(|a| a.as_str()) == Some("--sum") {
+        if !sum(&args[1..]) {
+            process::exit(1);
+        }
+        return;
+    }
+
+    let hash = KnotHasher::digest(include_str!("../input").trim().as_bytes());
+    println!("Hash is: {}", to_hex(&hash));
 }
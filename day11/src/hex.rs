@@ -0,0 +1,239 @@
+use std::str::FromStr;
+use std::error::Error;
+use std::fmt;
+
+// Flat-topped hexes, so north and south are neighbours in the same column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HexDirection {
+    N, NW, SW, S, SE, NE
+}
+
+impl HexDirection {
+    pub const ALL: [HexDirection; 6] = [HexDirection::N, HexDirection::NW, HexDirection::SW, HexDirection::S, HexDirection::SE, HexDirection::NE];
+}
+
+impl FromStr for HexDirection {
+    type Err = Box<Error>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "n" => Ok(HexDirection::N),
+            "nw" => Ok(HexDirection::NW),
+            "sw" => Ok(HexDirection::SW),
+            "s" => Ok(HexDirection::S),
+            "se" => Ok(HexDirection::SE),
+            "ne" => Ok(HexDirection::NE),
+            _ => Err(From::from("Invalid direction string.")),
+        }
+    }
+}
+
+impl fmt::Display for HexDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            HexDirection::N => "n",
+            HexDirection::NW => "nw",
+            HexDirection::SW => "sw",
+            HexDirection::S => "s",
+            HexDirection::SE => "se",
+            HexDirection::NE => "ne",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Cube coordinates; x + y + z is always 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HexPoint {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+// Axial coordinates: q is the column, r runs north to south within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Axial {
+    pub q: i32,
+    pub r: i32,
+}
+
+// "Odd-q" offset coordinates: odd columns sit half a hex lower than even ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Offset {
+    pub col: i32,
+    pub row: i32,
+}
+
+pub const ORIGIN: HexPoint = HexPoint { x: 0, y: 0, z: 0 };
+
+impl HexPoint {
+    pub fn to_axial(self) -> Axial {
+        Axial { q: self.y, r: self.z }
+    }
+
+    pub fn from_axial(a: Axial) -> HexPoint {
+        HexPoint { x: -a.q - a.r, y: a.q, z: a.r }
+    }
+
+    pub fn to_offset(self) -> Offset {
+        let Axial { q, r } = self.to_axial();
+        Offset { col: q, row: r + (q - (q & 1)) / 2 }
+    }
+
+    pub fn from_offset(o: Offset) -> HexPoint {
+        HexPoint::from_axial(Axial { q: o.col, r: o.row - (o.col - (o.col & 1)) / 2 })
+    }
+}
+
+pub fn add(point: HexPoint, dir: HexDirection) -> HexPoint {
+    let (xd, yd, zd) = match dir {
+        HexDirection::N  => (1, 0, -1),
+        HexDirection::NW => (1, -1, 0),
+        HexDirection::SW => (0, -1, 1),
+        HexDirection::S  => (-1, 0, 1),
+        HexDirection::SE => (-1, 1, 0),
+        HexDirection::NE => (0, 1, -1),
+    };
+    HexPoint { x: point.x + xd, y: point.y + yd, z: point.z + zd }
+}
+
+pub fn distance(a: HexPoint, b: HexPoint) -> u32 {
+    (i32::abs(a.x - b.x) + i32::abs(a.y - b.y) + i32::abs(a.z - b.z)) as u32 / 2
+}
+
+// The hexes exactly `radius` steps away, counterclockwise from the southernmost one.
+pub fn ring(center: HexPoint, radius: u32) -> Vec<HexPoint> {
+    if radius == 0 {
+        return vec![center];
+    }
+    let mut point = center;
+    for _ in 0..radius {
+        point = add(point, HexDirection::S);
+    }
+    let mut hexes = Vec::with_capacity(6 * radius as usize);
+    for dir in &[HexDirection::NE, HexDirection::N, HexDirection::NW, HexDirection::SW, HexDirection::S, HexDirection::SE] {
+        for _ in 0..radius {
+            hexes.push(point);
+            point = add(point, *dir);
+        }
+    }
+    hexes
+}
+
+// Every hex within `radius` steps, ring by ring outwards.
+pub fn spiral(center: HexPoint, radius: u32) -> Vec<HexPoint> {
+    (0..radius + 1).flat_map(|r| ring(center, r)).collect()
+}
+
+fn round(x: f64, y: f64, z: f64) -> HexPoint {
+    let (mut rx, mut ry, mut rz) = (x.round(), y.round(), z.round());
+    let (dx, dy, dz) = ((rx - x).abs(), (ry - y).abs(), (rz - z).abs());
+    // whichever coordinate rounded furthest is the one to fix up
+    if dx > dy && dx > dz {
+        rx = -ry - rz;
+    } else if dy > dz {
+        ry = -rx - rz;
+    } else {
+        rz = -rx - ry;
+    }
+    HexPoint { x: rx as i32, y: ry as i32, z: rz as i32 }
+}
+
+// The hexes a straight line from the centre of `a` to the centre of `b` passes through,
+// both ends included. Consecutive hexes are always neighbours.
+pub fn line(a: HexPoint, b: HexPoint) -> Vec<HexPoint> {
+    let n = distance(a, b);
+    if n == 0 {
+        return vec![a];
+    }
+    // nudged off the exact midpoints so ties between two hexes always break the same way
+    let (ax, ay, az) = (a.x as f64 + 1e-6, a.y as f64 + 2e-6, a.z as f64 - 3e-6);
+    let (bx, by, bz) = (b.x as f64 + 1e-6, b.y as f64 + 2e-6, b.z as f64 - 3e-6);
+    (0..n + 1).map(|i| {
+        let t = i as f64 / n as f64;
+        round(ax + (bx - ax) * t, ay + (by - ay) * t, az + (bz - az) * t)
+    }).collect()
+}
+
+// A minimal route from `a` to `b`: at most two directions, each direction's steps together.
+pub fn shortest_path(a: HexPoint, b: HexPoint) -> Vec<HexDirection> {
+    let mut path = Vec::with_capacity(distance(a, b) as usize);
+    let mut point = a;
+    while point != b {
+        let remaining = distance(point, b);
+        let dir = *HexDirection::ALL.iter().find(|d| distance(add(point, **d), b) < remaining).expect("Some neighbour is always closer.");
+        while point != b && distance(add(point, dir), b) < distance(point, b) {
+            point = add(point, dir);
+            path.push(dir);
+        }
+    }
+    path
+}
+
+// The shortest route to wherever a wandering list of steps ends up.
+pub fn canonicalize(steps: &[HexDirection]) -> Vec<HexDirection> {
+    shortest_path(ORIGIN, steps.iter().fold(ORIGIN, |p, d| add(p, *d)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk(start: HexPoint, steps: &[HexDirection]) -> HexPoint {
+        steps.iter().fold(start, |p, d| add(p, *d))
+    }
+
+    #[test]
+    fn coordinates_round_trip() {
+        for point in spiral(ORIGIN, 12) {
+            assert_eq!(point.x + point.y + point.z, 0);
+            assert_eq!(point, HexPoint::from_axial(point.to_axial()), "Axial round trip failed for {:?}", point);
+            assert_eq!(point, HexPoint::from_offset(point.to_offset()), "Offset round trip failed for {:?}", point);
+        }
+    }
+
+    #[test]
+    fn rings_and_spirals() {
+        let center = HexPoint { x: 2, y: -5, z: 3 };
+        assert_eq!(ring(center, 0), vec![center]);
+        for radius in 1..10 {
+            let hexes = ring(center, radius);
+            assert_eq!(hexes.len() as u32, 6 * radius);
+            assert!(hexes.iter().all(|h| distance(center, *h) == radius), "Ring {} strays from its radius", radius);
+            for (i, h) in hexes.iter().enumerate() {
+                assert_eq!(distance(*h, hexes[(i + 1) % hexes.len()]), 1, "Ring {} isn't contiguous at {}", radius, i);
+                assert!(!hexes[..i].contains(h), "Ring {} repeats {:?}", radius, h);
+            }
+            assert_eq!(spiral(center, radius).len() as u32, 3 * radius * (radius + 1) + 1);
+        }
+    }
+
+    #[test]
+    fn lines_are_contiguous() {
+        let a = HexPoint { x: 1, y: 2, z: -3 };
+        for b in spiral(ORIGIN, 8) {
+            let hexes = line(a, b);
+            assert_eq!(hexes.len() as u32, distance(a, b) + 1);
+            assert_eq!((hexes[0], hexes[hexes.len() - 1]), (a, b));
+            for pair in hexes.windows(2) {
+                assert_eq!(distance(pair[0], pair[1]), 1, "Line from {:?} to {:?} jumps", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn shortest_paths_are_minimal() {
+        let a = HexPoint { x: -4, y: 1, z: 3 };
+        for b in spiral(ORIGIN, 8) {
+            let path = shortest_path(a, b);
+            assert_eq!(path.len() as u32, distance(a, b), "Route from {:?} to {:?} isn't minimal", a, b);
+            assert_eq!(walk(a, &path), b);
+            let mut dirs = path.clone();
+            dirs.dedup();
+            assert!(dirs.len() <= 2, "Route from {:?} to {:?} changes direction more than once", a, b);
+        }
+        let wandering = [HexDirection::N, HexDirection::SE, HexDirection::S, HexDirection::NW, HexDirection::NE, HexDirection::NE, HexDirection::SW];
+        let route = canonicalize(&wandering);
+        assert_eq!(walk(ORIGIN, &route), walk(ORIGIN, &wandering));
+        assert_eq!(route.len() as u32, distance(ORIGIN, walk(ORIGIN, &wandering)));
+    }
+}
@@ -1,58 +1,112 @@
-use std::str::FromStr;
-use std::error::Error;
-use std::cmp;
+mod hex;
 
-#[derive(Debug, Clone, Copy)]
-enum HexDirection {
-    N, NW, SW, S, SE, NE
-}
+use std::env;
+use std::error::Error;
+use std::collections::HashMap;
+use hex::{HexDirection, HexPoint, Offset, ORIGIN, add, distance};
 
-impl FromStr for HexDirection {
-    type Err = Box<Error>;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "n" => Ok(HexDirection::N),
-            "nw" => Ok(HexDirection::NW),
-            "sw" => Ok(HexDirection::SW),
-            "s" => Ok(HexDirection::S),
-            "se" => Ok(HexDirection::SE),
-            "ne" => Ok(HexDirection::NE),
-            _ => Err(From::from("Invalid direction string.")),
+fn parse(input: &str) -> Result<Vec<HexDirection>, Box<Error>> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut dirs = Vec::new();
+    let mut unknown = Vec::new();
+    for (i, token) in input.split(',').enumerate() {
+        match token.trim().parse() {
+            Ok(dir) => { dirs.push(dir); }
+            Err(_) => { unknown.push(format!("{:?} (step {})", token.trim(), i + 1)); }
         }
     }
+    if unknown.is_empty() {
+        Ok(dirs)
+    } else {
+        Err(From::from(format!("Unknown directions: {}", unknown.join(", "))))
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct HexPoint {
-    pub x: i32,
-    pub y: i32,
-    pub z: i32,
+#[derive(Debug, Clone)]
+struct WalkSummary {
+    pub final_position: HexPoint,
+    pub final_distance: u32,
+    pub furthest_distance: u32,
+    pub furthest_step: usize, // counting from 1; 0 if the walk never leaves the origin
+    pub counts: HashMap<HexDirection, usize>,
 }
 
-fn add(point: HexPoint, dir: HexDirection) -> HexPoint {
-    let (xd, yd, zd) = match dir {
-        HexDirection::N  => (1, 0, -1),
-        HexDirection::NW => (1, -1, 0),
-        HexDirection::SW => (0, -1, 1),
-        HexDirection::S  => (-1, 0, 1),
-        HexDirection::SE => (-1, 1, 0),
-        HexDirection::NE => (0, 1, -1),
-    };
-    HexPoint { x: point.x + xd, y: point.y + yd, z: point.z + zd }
+fn walk(dirs: &[HexDirection]) -> WalkSummary {
+    let mut position = ORIGIN;
+    let mut furthest = (0, 0);
+    let mut counts = HashMap::new();
+    for (i, dir) in dirs.iter().enumerate() {
+        position = add(position, *dir);
+        *counts.entry(*dir).or_insert(0) += 1;
+        let d = distance(ORIGIN, position);
+        if d > furthest.0 {
+            furthest = (d, i + 1);
+        }
+    }
+    WalkSummary {
+        final_position: position,
+        final_distance: distance(ORIGIN, position),
+        furthest_distance: furthest.0,
+        furthest_step: furthest.1,
+        counts,
+    }
 }
 
-fn distance(a: HexPoint, b: HexPoint) -> u32 {
-    (i32::abs(a.x - b.x) + i32::abs(a.y - b.y) + i32::abs(a.z - b.z)) as u32 / 2
+// "n x 3, ne x 2" rather than five separate steps
+fn describe_route(route: &[HexDirection]) -> String {
+    let mut groups: Vec<(HexDirection, usize)> = Vec::new();
+    for dir in route {
+        match groups.last_mut() {
+            Some(&mut (last, ref mut count)) if last == *dir => { *count += 1; }
+            _ => { groups.push((*dir, 1)); }
+        }
+    }
+    let parts: Vec<String> = groups.iter().map(|&(dir, count)| format!("{} x {}", dir, count)).collect();
+    parts.join(", ")
+}
+
+fn show_offset(o: Offset) -> String {
+    format!("col {}, row {}", o.col, o.row)
 }
 
 fn main() {
-    let dirs = include_str!("../input").split(',').filter_map(|x| x.parse().ok());
-    let origin = HexPoint { x: 0, y: 0, z: 0 };
-    let mut position = origin;
-    let mut max_distance = 0;
-    for dir in dirs {
-        position = add(position, dir);
-        max_distance = cmp::max(max_distance, distance(origin, position));
-    }
-    println!("MAx distance from origin: {}", max_distance);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|a| a.as_str()) == Some("--spiral") {
+        let radius = args.get(1).and_then(|r| r.parse().ok()).expect("--spiral needs a radius.");
+        for point in hex::spiral(ORIGIN, radius) {
+            let (axial, offset) = (point.to_axial(), point.to_offset());
+            println!("({}, {}, {})  q {}, r {}  {}", point.x, point.y, point.z, axial.q, axial.r, show_offset(offset));
+        }
+        return;
+    }
+
+    let dirs = match parse(include_str!("../input")) {
+        Ok(dirs) => dirs,
+        Err(e) => { println!("{}", e); return; }
+    };
+    let summary = walk(&dirs);
+    let position = summary.final_position;
+    println!("Final position: {} ({} steps from origin)", show_offset(position.to_offset()), summary.final_distance);
+    println!("Max distance from origin: {} (after step {})", summary.furthest_distance, summary.furthest_step);
+    let counts: Vec<String> = HexDirection::ALL.iter().map(|d| format!("{} {}", d, summary.counts.get(d).unwrap_or(&0))).collect();
+    println!("Steps taken: {} ({})", dirs.len(), counts.join(", "));
+
+    if args.iter().any(|a| a == "--route") {
+        let route = hex::canonicalize(&dirs);
+        println!("Shortest route to {}: {}", show_offset(position.to_offset()), describe_route(&route));
+    }
+    // to the final position, or to the hex at `--line col row`
+    if let Some(i) = args.iter().position(|a| a == "--line") {
+        let coordinate = |j: usize| args.get(i + j).and_then(|n| n.parse().ok());
+        let target = match (coordinate(1), coordinate(2)) {
+            (Some(col), Some(row)) => HexPoint::from_offset(Offset { col, row }),
+            _ => position,
+        };
+        for point in hex::line(ORIGIN, target) {
+            println!("{}", show_offset(point.to_offset()));
+        }
+    }
 }
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use Id;
+
+// Maps node names to dense indices 0, 1, 2, ... in order of first appearance.
+#[derive(Debug, Clone, Default)]
+pub struct Interner<'a> {
+    index: HashMap<&'a str, u32>,
+    names: Vec<&'a str>,
+}
+
+impl<'a> Interner<'a> {
+    pub fn intern(&mut self, name: &'a str) -> u32 {
+        if let Some(i) = self.index.get(name) {
+            return *i;
+        }
+        let i = self.names.len() as u32;
+        self.index.insert(name, i);
+        self.names.push(name);
+        i
+    }
+
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.index.get(name).cloned()
+    }
+
+    pub fn name(&self, i: u32) -> &'a str {
+        self.names[i as usize]
+    }
+}
+
+// Compressed sparse row adjacency: node i's neighbours are targets[offsets[i]..offsets[i + 1]].
+#[derive(Debug, Clone)]
+pub struct Csr {
+    offsets: Vec<usize>,
+    targets: Vec<u32>,
+}
+
+impl Csr {
+    // pipes go both ways, and repeats (the puzzle lists every pipe from both ends) are dropped
+    pub fn from_edges(nodes: usize, edges: &mut Vec<(u32, u32)>) -> Csr {
+        for edge in edges.iter_mut() {
+            if edge.0 > edge.1 {
+                *edge = (edge.1, edge.0);
+            }
+        }
+        edges.sort_unstable();
+        edges.dedup();
+        let mut offsets = vec![0; nodes + 1];
+        for &(a, b) in edges.iter() {
+            offsets[a as usize + 1] += 1;
+            if a != b {
+                offsets[b as usize + 1] += 1;
+            }
+        }
+        for i in 0..nodes {
+            offsets[i + 1] += offsets[i];
+        }
+        let mut targets = vec![0; offsets[nodes]];
+        let mut next = offsets.clone();
+        for &(a, b) in edges.iter() {
+            targets[next[a as usize]] = b;
+            next[a as usize] += 1;
+            if a != b {
+                targets[next[b as usize]] = a;
+                next[b as usize] += 1;
+            }
+        }
+        Csr { offsets, targets }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn neighbors(&self, node: u32) -> &[u32] {
+        &self.targets[self.offsets[node as usize]..self.offsets[node as usize + 1]]
+    }
+
+    // every node reachable from `start`, itself included
+    pub fn reachable(&self, start: u32) -> Vec<u32> {
+        let mut seen = vec![false; self.node_count()];
+        let mut found = vec![start];
+        seen[start as usize] = true;
+        let mut i = 0;
+        while i < found.len() {
+            for &next in self.neighbors(found[i]) {
+                if !seen[next as usize] {
+                    seen[next as usize] = true;
+                    found.push(next);
+                }
+            }
+            i += 1;
+        }
+        found
+    }
+
+    // a group number for every node, and how many groups there are; iterative, so a long
+    // chain of pipes can't overflow the stack
+    pub fn components(&self) -> (Vec<u32>, u32) {
+        const UNSEEN: u32 = ::std::u32::MAX;
+        let mut colors = vec![UNSEEN; self.node_count()];
+        let mut next_color = 0;
+        let mut stack = Vec::new();
+        for start in 0..self.node_count() {
+            if colors[start] != UNSEEN {
+                continue;
+            }
+            colors[start] = next_color;
+            stack.push(start as u32);
+            while let Some(node) = stack.pop() {
+                for &other in self.neighbors(node) {
+                    if colors[other as usize] == UNSEEN {
+                        colors[other as usize] = next_color;
+                        stack.push(other);
+                    }
+                }
+            }
+            next_color += 1;
+        }
+        (colors, next_color)
+    }
+}
+
+// The string-named view of a network, stored as interned indices over a Csr.
+#[derive(Debug, Clone)]
+pub struct Graph<'a> {
+    names: Interner<'a>,
+    adjacency: Csr,
+}
+
+impl<'a> Graph<'a> {
+    pub fn new<I>(entries: I) -> Graph<'a> where I: IntoIterator<Item=(Id<'a>, Vec<Id<'a>>)> {
+        let mut names = Interner::default();
+        let mut edges = Vec::new();
+        for (from, nodes) in entries {
+            let from = names.intern(from.0);
+            for to in nodes {
+                edges.push((from, names.intern(to.0)));
+            }
+        }
+        let adjacency = Csr::from_edges(names.names.len(), &mut edges);
+        Graph { names, adjacency }
+    }
+
+    pub fn neighbors(&self, node: Id) -> Vec<Id<'a>> {
+        self.names.get(node.0).map_or(Vec::new(), |i| self.adjacency.neighbors(i).iter().map(|n| Id(self.names.name(*n))).collect())
+    }
+
+    // every node in the same group as `node`, or nothing if it isn't in the graph
+    pub fn group(&self, node: Id) -> Vec<Id<'a>> {
+        self.names.get(node.0).map_or(Vec::new(), |i| self.adjacency.reachable(i).iter().map(|n| Id(self.names.name(*n))).collect())
+    }
+
+    pub fn group_count(&self) -> u32 {
+        self.adjacency.components().1
+    }
+}
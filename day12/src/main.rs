@@ -1,72 +1,90 @@
 #![feature(io)]
 
-#[macro_use]
-extern crate lazy_static;
-extern crate regex;
 extern crate itertools;
 
+mod network;
+mod graph;
+
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
-use regex::Regex;
 use std::error::Error;
-use std::collections::{HashMap, HashSet};
+use std::env;
 use itertools::Itertools;
-
-lazy_static! {
-    static ref LINE_RE: Regex = Regex::new(r#"(?P<node>\w+) <-> (?P<others>.*)"#).unwrap();
-}
+use network::Network;
+use graph::Graph;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Id<'a>(pub &'a str);
 
-#[derive(Debug, Clone)]
-struct Graph<'a> {
-    pub edges: HashMap<Id<'a>, HashSet<Id<'a>>>,
+fn parse(line: &str) -> Result<(Id<'_>, Vec<Id<'_>>), Box<Error>> {
+    let mut parts = line.splitn(2, " <-> ");
+    let node = parts.next().unwrap().trim();
+    let others = parts.next().ok_or("Failed to parse line.")?;
+    if node.is_empty() || node.contains(char::is_whitespace) {
+        return Err(From::from("Failed to parse line."));
+    }
+    Ok((Id(node), others.split(',').map(|n| Id(n.trim())).collect()))
 }
 
-fn color_all<'a>(graph: &Graph<'a>) -> HashMap<Id<'a>, u32> {
-    let mut colors = HashMap::new();
-    let mut next_color = 0;
-    for node in graph.edges.keys() {
-        if colors.get(node).is_none() {
-            colors.insert(*node, next_color);
-            color_connected(&mut colors, graph, node, next_color);
-            next_color += 1;
+// One command per line, answered in order as the network grows:
+//   a <-> b, c       pipes in the puzzle's own format
+//   pipe a b         a single pipe
+//   connected a b    whether a and b are in the same group
+//   group a          the node currently representing a's group
+//   size a           how many nodes are in a's group
+//   count            how many groups there are
+// Blank lines and lines starting with '#' are ignored.
+fn run_queries(path: &str) -> Result<(), Box<Error>> {
+    let mut text = String::new();
+    File::open(path)?.read_to_string(&mut text)?;
+    let mut network = Network::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
         }
-    }
-    colors
-} 
-
-fn color_connected<'a>(colors: &mut HashMap<Id<'a>, u32>, graph: &Graph<'a>, to: &Id<'a>, color: u32) {
-    if let Some(others) = graph.edges.get(to) {
-        for other in others {
-            if colors.get(other).is_none() {
-                colors.insert(*other, color);
-                color_connected(colors, graph, other, color);
+        if line.contains("<->") {
+            let (from, nodes) = parse(line).map_err(|e| format!("Line {}: {}", n + 1, e))?;
+            network.add_node(from);
+            for to in nodes {
+                network.add_pipe(from, to);
             }
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match (words[0], words.len()) {
+            ("pipe", 3) => { network.add_pipe(Id(words[1]), Id(words[2])); }
+            ("connected", 3) => println!("{}: {}", line, network.connected(Id(words[1]), Id(words[2]))),
+            ("group", 2) => println!("{}: {}", line, network.group_of(Id(words[1])).map_or("unknown", |g| g.0)),
+            ("size", 2) => println!("{}: {}", line, network.group_size(Id(words[1])).unwrap_or(0)),
+            ("count", 1) => println!("{}: {}", line, network.group_count()),
+            _ => { return Err(From::from(format!("Line {}: unrecognized query {:?}.", n + 1, line))); }
         }
     }
-}
-
-fn parse(line: &str) -> Result<(Id, Vec<Id>), Box<Error>> {
-    let parts = LINE_RE.captures(line).ok_or("Failed to parse line.")?;
-    let node = parts.name("node").unwrap().as_str();
-    let others = parts.name("others").unwrap().as_str();
-    Ok((Id(node), others.split(", ").map(|n| Id(n)).collect()))
+    Ok(())
 }
 
 fn main() {
-    let reader = BufReader::new(File::open("input").expect("Couldn't read input file."));
-    let lines: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
-    let entries = lines.iter().map(|l| parse(l).expect("Couldn't parse line."));
-    let mut graph = Graph { edges: HashMap::new() };
-    for (from, nodes) in entries {
-        for to in nodes {
-            graph.edges.entry(from).or_insert_with(HashSet::new).insert(to);
-            graph.edges.entry(to).or_insert_with(HashSet::new).insert(from);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|a| a.as_str()) == Some("--queries") {
+        let path = args.get(1).expect("--queries needs a file.");
+        if let Err(e) = run_queries(path) {
+            println!("{}", e);
         }
+        return;
+    }
+
+    let path = match args.first() {
+        Some(p) if !p.starts_with("--") => p.as_str(),
+        _ => "input",
+    };
+    let mut text = String::new();
+    File::open(path).expect("Couldn't read input file.").read_to_string(&mut text).expect("Couldn't read input file.");
+    let graph = Graph::new(text.lines().map(|l| parse(l).expect("Couldn't parse line.")));
+    println!("Number of groups: {}", graph.group_count());
+    if let Some(i) = args.iter().position(|a| a == "--group") {
+        let node = Id(args.get(i + 1).expect("--group needs a node."));
+        let neighbors = graph.neighbors(node).iter().map(|n| n.0).join(", ");
+        println!("Group containing {}: {} nodes; it pipes directly to {}", node.0, graph.group(node).len(), neighbors);
     }
-    let colors = color_all(&graph);
-    println!("Number of groups: {}", colors.values().unique().count());
 }
@@ -0,0 +1,85 @@
+use graph::Interner;
+use Id;
+
+// A pipe network that can grow one pipe at a time while answering connectivity queries, using
+// union-find. Union by size together with path halving makes every operation take amortized
+// inverse-Ackermann time, which is constant for any network that fits in memory. Halving
+// rewrites parents as it goes, so even queries take &mut self. Nodes get the same dense u32
+// indices as in a Graph; names only come into it when a query starts or ends.
+#[derive(Debug, Clone, Default)]
+pub struct Network<'a> {
+    names: Interner<'a>,
+    parent: Vec<u32>,
+    size: Vec<u32>, // only meaningful for roots
+    groups: usize,
+}
+
+impl<'a> Network<'a> {
+    pub fn new() -> Self {
+        Network::default()
+    }
+
+    // a node with no pipes is a group of its own
+    pub fn add_node(&mut self, node: Id<'a>) -> u32 {
+        let i = self.names.intern(node.0);
+        if i as usize == self.parent.len() {
+            self.parent.push(i);
+            self.size.push(1);
+            self.groups += 1;
+        }
+        i
+    }
+
+    // true if the pipe joined two groups that weren't already connected
+    pub fn add_pipe(&mut self, a: Id<'a>, b: Id<'a>) -> bool {
+        let (a, b) = (self.add_node(a), self.add_node(b));
+        let (mut a, mut b) = (self.root(a), self.root(b));
+        if a == b {
+            return false;
+        }
+        if self.size[a as usize] < self.size[b as usize] {
+            ::std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b as usize] = a;
+        self.size[a as usize] += self.size[b as usize];
+        self.groups -= 1;
+        true
+    }
+
+    // every node on the way up is pointed at its grandparent, halving the path for next time
+    fn root(&mut self, mut i: u32) -> u32 {
+        while self.parent[i as usize] != i {
+            let grandparent = self.parent[self.parent[i as usize] as usize];
+            self.parent[i as usize] = grandparent;
+            i = grandparent;
+        }
+        i
+    }
+
+    fn root_of(&mut self, node: Id) -> Option<u32> {
+        let i = self.names.get(node.0)?;
+        Some(self.root(i))
+    }
+
+    pub fn connected(&mut self, a: Id, b: Id) -> bool {
+        match (self.root_of(a), self.root_of(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    // the node representing a's group; it can change as groups merge
+    pub fn group_of(&mut self, node: Id) -> Option<Id<'a>> {
+        let r = self.root_of(node)?;
+        Some(Id(self.names.name(r)))
+    }
+
+    pub fn group_size(&mut self, node: Id) -> Option<usize> {
+        let r = self.root_of(node)?;
+        Some(self.size[r as usize] as usize)
+    }
+
+    pub fn group_count(&self) -> usize {
+        self.groups
+    }
+}
@@ -2,45 +2,204 @@
 
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
 use std::error::Error;
 use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::thread;
+use std::time::Duration;
 
-fn scanner_position(range: u32, t: u32) -> u32 {
-    let span = (range - 1) * 2;
+mod solver;
+mod simulate;
+
+// Range 0 layers never get this far; parse_firewall rejects them.
+fn scanner_position(range: u32, t: u64) -> u32 {
+    if range == 1 {
+        return 0; // nowhere to go, so it's always at the top
+    }
+    let span = (range as u64 - 1) * 2;
     let offset = t % span;
-    if offset > range - 1 {
-        span - offset
+    if offset > range as u64 - 1 {
+        (span - offset) as u32
     } else {
-        offset
+        offset as u32
     }
 }
 
-fn caught(firewall: &HashMap<u32, u32>, delay: u32) -> bool {
-    let max_layer = firewall.keys().max().unwrap();
-    for depth in 0..(max_layer + 1) {
-        if let Some(range) = firewall.get(&depth) {
-            if scanner_position(*range, depth + delay) == 0 {
-                return true;
-            }
+fn parse(line: &str) -> Result<(u32, u32), Box<Error>> {
+    let parts: Vec<&str> = line.split(": ").collect();
+    if parts.len() != 2 {
+        return Err(From::from("Failed to parse line."));
+    }
+    let (depth, range) = (parts[0].parse()?, parts[1].parse()?);
+    if range == 0 {
+        Err(From::from("A layer's range must be at least 1."))
+    } else {
+        Ok((depth, range))
+    }
+}
+
+// blank lines are skipped; errors say which line they're on
+fn parse_firewall(text: &str) -> Result<HashMap<u32, u32>, Box<Error>> {
+    let mut firewall = HashMap::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (depth, range) = parse(line).map_err(|e| format!("Line {}: {}", n + 1, e))?;
+        if firewall.insert(depth, range).is_some() {
+            return Err(From::from(format!("Line {}: layer {} is listed twice.", n + 1, depth)));
         }
     }
-    false
+    Ok(firewall)
 }
 
-fn parse(line: &str) -> Result<(u32, u32), Box<Error>> {
-    let parts: Vec<&str> = line.split(": ").collect();
-    if parts.len() != 2 {
-        Err(From::from("Failed to parse line."))
+// prints every frame, redraws them in place with --animate [ms], or writes them all to --log path
+fn show_simulation(firewall: &HashMap<u32, u32>, delay: u32, args: &[String]) {
+    if let Some(range) = firewall.values().find(|r| **r > simulate::MAX_DRAWN_RANGE) {
+        println!("A range {} scanner is too tall to draw; the simulation only draws ranges up to {}.", range, simulate::MAX_DRAWN_RANGE);
+        return;
+    }
+    let frames = simulate::simulate(firewall, delay);
+    let option = |name: &str| args.iter().position(|a| a == name).map(|i| args.get(i + 1));
+    if let Some(path) = option("--log") {
+        let path = path.expect("--log needs a file.");
+        let mut log = File::create(path).expect("Couldn't create log file.");
+        for frame in &frames {
+            writeln!(log, "{}", simulate::render(frame)).expect("Couldn't write log file.");
+        }
+        println!("Wrote {} frames to {}.", frames.len(), path);
+    } else if let Some(ms) = option("--animate") {
+        let pause = Duration::from_millis(ms.and_then(|ms| ms.parse().ok()).unwrap_or(300));
+        for frame in &frames {
+            print!("\x1b[2J\x1b[H{}", simulate::render(frame));
+            io::stdout().flush().expect("Couldn't write frame.");
+            thread::sleep(pause);
+        }
     } else {
-        Ok((parts[0].parse()?, parts[1].parse()?))
+        for frame in &frames {
+            println!("{}", simulate::render(frame));
+        }
+    }
+    let caught_at: Vec<String> = frames.iter().filter(|f| f.caught).map(|f| f.packet.to_string()).collect();
+    if caught_at.is_empty() {
+        println!("Delay {}: got through safely.", delay);
+    } else {
+        println!("Delay {}: caught in layers {}.", delay, caught_at.join(", "));
     }
 }
 
 fn main() {
-    let reader = BufReader::new(File::open("input").expect("Couldn't read input file."));
-    let lines: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
-    let firewall: HashMap<u32, u32> = lines.iter().map(|l| parse(l).expect("Couldn't parse line.")).collect();
-    let first_uncaught_delay = (0..).find(|n| !caught(&firewall, *n)).unwrap();
-    println!("Best delay: {}", first_uncaught_delay);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut text = String::new();
+    File::open("input").expect("Couldn't read input file.").read_to_string(&mut text).expect("Couldn't read input file.");
+    let firewall = match parse_firewall(&text) {
+        Ok(firewall) => firewall,
+        Err(e) => { println!("{}", e); return; }
+    };
+    if let Some(i) = args.iter().position(|a| a == "--simulate") {
+        let delay = args.get(i + 1).and_then(|d| d.parse().ok()).unwrap_or(0);
+        return show_simulation(&firewall, delay, &args);
+    }
+    println!("Trip severity: {}", solver::severity(&firewall, 0));
+    match solver::safe_delay(&firewall) {
+        Some(delay) => println!("Best delay: {}", delay),
+        None => println!("No delay gets through safely."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic;
+
+    // the puzzle's own definition, which the solver and the simulation are checked against
+    fn caught(firewall: &HashMap<u32, u32>, delay: u32) -> bool {
+        firewall.iter().any(|(&depth, &range)| scanner_position(range, depth as u64 + delay as u64) == 0)
+    }
+
+    // xorshift32: the same random firewalls on every run, without pulling in rand
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+    }
+
+    #[test]
+    fn simulation_agrees_with_caught() {
+        let mut rng = Rng(2463534242);
+        for trial in 0..300 {
+            let layers = 1 + rng.next() % 12;
+            let firewall: HashMap<u32, u32> = (0..layers).map(|_| (rng.next() % 30, 1 + rng.next() % 10)).collect();
+            let delay = rng.next() % 1000;
+            let frames = simulate::simulate(&firewall, delay);
+            assert_eq!(frames.iter().any(|f| f.caught), caught(&firewall, delay), "Simulation disagrees with caught on trial {}: {:?}", trial, firewall);
+        }
+    }
+
+    // small random firewalls, so brute force stays quick, checked against the solver
+    #[test]
+    fn solver_matches_brute_force() {
+        let mut rng = Rng(2463534242);
+        for trial in 0..300 {
+            let layers = 1 + rng.next() % 12;
+            let firewall: HashMap<u32, u32> = (0..layers).map(|_| (rng.next() % 30, 1 + rng.next() % 10)).collect();
+            let expected = (0..100_000).find(|n| !caught(&firewall, *n)).map(|n| n as u64);
+            assert_eq!(expected, solver::safe_delay(&firewall), "Solver disagrees with brute force on trial {}: {:?}", trial, firewall);
+            let brute_severity: u64 = firewall.iter().filter(|&(&d, &r)| scanner_position(r, d as u64) == 0).map(|(&d, &r)| (d * r) as u64).sum();
+            assert_eq!(brute_severity, solver::severity(&firewall, 0), "Severity disagrees on trial {}!", trial);
+        }
+    }
+
+    // Arbitrary text, biased towards things that look like firewalls: numbers of every size
+    // (including 0, 1 and ones too big for a u32), separators in the right and wrong places.
+    fn fuzz_input(rng: &mut Rng) -> String {
+        let pieces = ["0", "1", "2", "7", ": ", ":", " ", "\n", "\n", "-", "x", "\t", "4294967295", "4294967296", "99999999999"];
+        let mut input = String::new();
+        for _ in 0..rng.next() % 40 {
+            if rng.next() % 3 == 0 {
+                let digits = 1 + rng.next() % 6;
+                for _ in 0..digits {
+                    input.push((b'0' + (rng.next() % 10) as u8) as char);
+                }
+            } else {
+                input.push_str(pieces[(rng.next() as usize) % pieces.len()]);
+            }
+        }
+        input
+    }
+
+    // Feeds arbitrary input to parse_firewall and every firewall it accepts to caught, checking
+    // caught against the solver's residue tables and that neither ever panics.
+    #[test]
+    fn fuzz_parse_and_caught() {
+        let mut rng = Rng(2463534242);
+        for trial in 0..2000 {
+            let input = fuzz_input(&mut rng);
+            let delays = [0, 1, rng.next(), ::std::u32::MAX];
+            let result = panic::catch_unwind(|| {
+                let firewall = match parse_firewall(&input) {
+                    Ok(firewall) => firewall,
+                    Err(e) => {
+                        assert!(e.to_string().starts_with("Line "), "Error without a line number: {}", e);
+                        return;
+                    }
+                };
+                assert!(firewall.values().all(|r| *r > 0), "A range 0 layer was accepted!");
+                let residues = solver::forbidden(&firewall);
+                let always_caught = firewall.values().any(|r| *r == 1);
+                for &delay in &delays {
+                    assert_eq!(solver::forbids(&residues, delay as u64), caught(&firewall, delay), "caught disagrees with the residue tables at delay {}", delay);
+                    assert!(!always_caught || caught(&firewall, delay), "A range 1 layer let delay {} through!", delay);
+                }
+            });
+            assert!(result.is_ok(), "Trial {} failed on input {:?}", trial, input);
+        }
+    }
 }
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use scanner_position;
+
+// Scanners taller than this would draw a row per position, so show_simulation refuses them.
+pub const MAX_DRAWN_RANGE: u32 = 64;
+
+// The firewall during one picosecond: the packet has just moved into the layer at depth
+// `packet`, and the scanners haven't moved yet.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub time: u64,
+    pub packet: u32,
+    pub scanners: Vec<(u32, u32, u32)>, // (depth, range, position) of every layer, shallowest first
+    pub caught: bool,
+}
+
+// One frame for each layer the packet moves into. Only layers can catch it, so the gaps
+// between them get neither frames nor columns, however deep the firewall goes.
+pub fn simulate(firewall: &HashMap<u32, u32>, delay: u32) -> Vec<Frame> {
+    let mut layers: Vec<(u32, u32)> = firewall.iter().map(|(d, r)| (*d, *r)).collect();
+    layers.sort();
+    layers.iter().map(|&(depth, range)| {
+        let time = delay as u64 + depth as u64;
+        let scanners = layers.iter().map(|&(d, r)| (d, r, scanner_position(r, time))).collect();
+        Frame { time, packet: depth, scanners, caught: scanner_position(range, time) == 0 }
+    }).collect()
+}
+
+// without trailing spaces, which the short columns at the right would otherwise leave
+fn push_line(out: &mut String, line: &str) {
+    out.push_str(line);
+    while out.ends_with(' ') {
+        out.pop();
+    }
+    out.push('\n');
+}
+
+// Drawn like the puzzle's own diagrams: [S] is a scanner and ( ) the packet. Columns are
+// labelled with their depth, since gaps in the firewall aren't drawn.
+pub fn render(frame: &Frame) -> String {
+    let mut out = String::new();
+    write!(&mut out, "Picosecond {}: packet in layer {}", frame.time, frame.packet).unwrap();
+    out.push_str(if frame.caught { ", caught!\n" } else { "\n" });
+    let width = frame.scanners.iter().map(|s| s.0.to_string().len()).max().unwrap_or(0).max(3);
+    let depths: Vec<String> = frame.scanners.iter().map(|s| format!("{:<1$}", s.0, width)).collect();
+    push_line(&mut out, &depths.join(" "));
+    let rows = frame.scanners.iter().map(|s| s.1).max().unwrap_or(1);
+    for row in 0..rows {
+        let cells: Vec<String> = frame.scanners.iter().map(|&(depth, range, position)| {
+            let (open, close) = if row == 0 && depth == frame.packet { ('(', ')') } else { ('[', ']') };
+            let cell = if row < range { format!("{}{}{}", open, if position == row { 'S' } else { ' ' }, close) } else { String::new() };
+            format!("{:<1$}", cell, width)
+        }).collect();
+        push_line(&mut out, &cells.join(" "));
+    }
+    out
+}
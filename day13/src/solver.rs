@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashMap};
+use std::cmp;
+
+// Residues are only combined into the wheel while its modulus stays this small.
+const WHEEL_LIMIT: u64 = 1 << 20;
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// A range 1 scanner never moves, so its layer catches everything: period 1, residue 0.
+pub fn period(range: u32) -> u64 {
+    cmp::max(1, (range as u64 - 1) * 2)
+}
+
+// A packet delayed by `delay` reaches layer `depth` at time delay + depth, and the scanner there is
+// back at the top every period(range) picoseconds, so the layer forbids exactly the delays
+// congruent to -depth modulo that period. Grouped by period, as sorted forbidden residues.
+pub fn forbidden(firewall: &HashMap<u32, u32>) -> BTreeMap<u64, Vec<u64>> {
+    let mut residues = BTreeMap::new();
+    for (&depth, &range) in firewall {
+        let period = period(range);
+        residues.entry(period).or_insert_with(Vec::new).push((period - depth as u64 % period) % period);
+    }
+    for forbidden in residues.values_mut() {
+        forbidden.sort_unstable();
+        forbidden.dedup();
+    }
+    residues
+}
+
+pub fn forbids(residues: &BTreeMap<u64, Vec<u64>>, delay: u64) -> bool {
+    residues.iter().any(|(period, forbidden)| forbidden.binary_search(&(delay % period)).is_ok())
+}
+
+// The smallest delay that no layer forbids, if there is one. The smallest periods are combined
+// CRT-style into a wheel of residues that are allowed modulo their LCM; candidates are then drawn
+// from the wheel in increasing order and checked against the remaining periods. Everything
+// repeats after the LCM of all the periods, so finding nothing by then means nothing is safe.
+pub fn safe_delay(firewall: &HashMap<u32, u32>) -> Option<u64> {
+    let residues = forbidden(firewall);
+    let mut wheel_size = 1;
+    let mut wheel = vec![0];
+    let mut rest = BTreeMap::new();
+    let mut cycle = Some(1u64);
+    for (&period, forbidden) in &residues {
+        cycle = cycle.and_then(|c| (c / gcd(c, period)).checked_mul(period));
+        let combined = wheel_size / gcd(wheel_size, period) * period;
+        if combined > WHEEL_LIMIT {
+            rest.insert(period, forbidden.clone());
+            continue;
+        }
+        let mut table = vec![false; period as usize];
+        for r in forbidden {
+            table[*r as usize] = true;
+        }
+        // every residue mod wheel_size extends to combined / wheel_size residues mod combined
+        let mut next = Vec::new();
+        for k in 0..combined / wheel_size {
+            for &r in &wheel {
+                let candidate = r + k * wheel_size;
+                if !table[(candidate % period) as usize] {
+                    next.push(candidate);
+                }
+            }
+        }
+        next.sort_unstable();
+        wheel = next;
+        wheel_size = combined;
+    }
+    if wheel.is_empty() {
+        return None;
+    }
+    let limit = cycle.unwrap_or(::std::u64::MAX);
+    let mut base = 0u64;
+    while base < limit {
+        for &r in &wheel {
+            let delay = base.checked_add(r)?;
+            if delay >= limit {
+                return None;
+            }
+            if !forbids(&rest, delay) {
+                return Some(delay);
+            }
+        }
+        base = base.checked_add(wheel_size)?;
+    }
+    None
+}
+
+// the sum of depth * range over every layer that catches a packet sent at `delay`
+pub fn severity(firewall: &HashMap<u32, u32>, delay: u64) -> u64 {
+    firewall.iter()
+        .filter(|&(&depth, &range)| (delay + depth as u64) % period(range) == 0)
+        .map(|(&depth, &range)| depth as u64 * range as u64)
+        .sum()
+}
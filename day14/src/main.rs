@@ -1,16 +1,12 @@
-extern crate itertools;
+extern crate rayon;
 
-use std::collections::{HashMap, HashSet};
-use itertools::Itertools;
-
-fn pinch(xs: &mut [u8], pos: usize, length: usize) {
-    let count = length / 2;
-    for offset in 0..count {
-        let i = (pos + offset) % xs.len();
-        let j = (pos + length - offset - 1) % xs.len();
-        xs.swap(i, j);
-    }
-}
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use rayon::prelude::*;
 
 fn xor(xs: &[u8]) -> u8 {
     let mut output = 0;
@@ -24,82 +20,187 @@ fn compact(xs: &[u8], block_size: usize) -> Vec<u8> {
     xs.chunks(block_size).map(xor).collect()
 }
 
-fn initialize(length: usize) -> Vec<u8> {
-    let mut xs = Vec::with_capacity(length);
-    for i in 0..length {
-        xs.push(i as u8);
+// Reversals index the ring directly until one would run past its end; then the ring is rotated
+// so the current position is at index 0, which makes that reversal (and the next few) a plain
+// slice reverse. `origin` tracks where the real index 0 has ended up. The same as day10's
+// knot::sparse_hash, where `day10 --bench` times it against the original rounds on rows like these.
+fn sparse_hash(input: &[u8], rounds: u32) -> [u8; 256] {
+    let mut ring = [0u8; 256];
+    for (i, x) in ring.iter_mut().enumerate() {
+        *x = i as u8;
     }
-    xs
-}
-
-fn hashify(input: &[u8], rounds: u32) -> Vec<u8> {
-    let mut skip = 0;
+    let mut origin = 0;
     let mut pos = 0;
-    let mut sparse = initialize(256);
+    let mut skip = 0;
     for _ in 0..rounds {
-        for x in input {
-            let length = *x as usize;
-            pinch(&mut sparse, pos, length);
-            pos += skip + length;
-            skip += 1;
+        for &length in input {
+            let length = length as usize;
+            if pos + length > 256 {
+                ring.rotate_left(pos);
+                origin = (origin + 256 - pos) % 256;
+                pos = 0;
+            }
+            ring[pos..pos + length].reverse();
+            pos = (pos + length + skip) % 256;
+            skip = (skip + 1) % 256;
         }
     }
-    compact(&mut sparse, 16)
+    ring.rotate_left(origin);
+    ring
+}
+
+fn hashify(input: &[u8], rounds: u32) -> Vec<u8> {
+    compact(&sparse_hash(input, rounds), 16)
 }
 
 #[derive(Debug, Clone)]
 pub struct Grid {
-    pub nodes: HashSet<(i32, i32)>,
+    pub nodes: HashSet<(i32, i32)>, // (column, row) of every used square
+    pub width: usize,
+    pub height: usize,
 }
 
-fn color_all(grid: &Grid) -> HashMap<(i32, i32), u32> {
-    let mut colors = HashMap::new();
-    let mut next_color = 0;
-    for node in grid.nodes.iter() {
-        if !colors.contains_key(node) {
-            colors.insert(*node, next_color);
-            color_connected(&grid, &mut colors, &node, next_color);
-            next_color += 1;
+// Region IDs for every square in row-major order, None for free squares. Regions are numbered
+// in the order their first square appears reading left to right, top to bottom, so the same
+// grid always gets the same labels.
+fn label_regions(grid: &Grid) -> (Vec<Option<u32>>, u32) {
+    let index = |x: i32, y: i32| y as usize * grid.width + x as usize;
+    let mut labels = vec![None; grid.width * grid.height];
+    let mut next_label = 0;
+    let mut stack = Vec::new();
+    for y in 0..grid.height as i32 {
+        for x in 0..grid.width as i32 {
+            if !grid.nodes.contains(&(x, y)) || labels[index(x, y)].is_some() {
+                continue;
+            }
+            labels[index(x, y)] = Some(next_label);
+            stack.push((x, y));
+            while let Some((x0, y0)) = stack.pop() {
+                for &(xd, yd) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+                    let neighbor = (x0 + xd, y0 + yd);
+                    if grid.nodes.contains(&neighbor) && labels[index(neighbor.0, neighbor.1)].is_none() {
+                        labels[index(neighbor.0, neighbor.1)] = Some(next_label);
+                        stack.push(neighbor);
+                    }
+                }
+            }
+            next_label += 1;
         }
     }
-    colors
-} 
+    (labels, next_label)
+}
 
-fn color_connected(grid: &Grid, colors: &mut HashMap<(i32, i32), u32>, to: &(i32, i32), color: u32) {
-    let &(x0, y0) = to;
-    let dirs: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
-    for &(xd, yd) in dirs.iter() {
-        let neighbor = (x0 + xd, y0 + yd);
-        if grid.nodes.contains(&neighbor) && !colors.contains_key(&neighbor) {
-            colors.insert(neighbor, color);
-            color_connected(grid, colors, &neighbor, color);
-        }
+// region size -> how many regions have that size
+fn size_histogram(labels: &[Option<u32>], regions: u32) -> BTreeMap<usize, usize> {
+    let mut sizes = vec![0; regions as usize];
+    for label in labels.iter().filter_map(|l| *l) {
+        sizes[label as usize] += 1;
+    }
+    let mut histogram = BTreeMap::new();
+    for size in sizes {
+        *histogram.entry(size).or_insert(0) += 1;
+    }
+    histogram
+}
+
+// plain PBM: 1 (black) for used squares
+fn write_pbm(grid: &Grid, out: &mut Write) -> io::Result<()> {
+    writeln!(out, "P1\n{} {}", grid.width, grid.height)?;
+    for y in 0..grid.height as i32 {
+        let row: Vec<&str> = (0..grid.width as i32).map(|x| if grid.nodes.contains(&(x, y)) { "1" } else { "0" }).collect();
+        writeln!(out, "{}", row.join(" "))?;
+    }
+    Ok(())
+}
+
+// plain PGM: free squares are black and each region gets its own shade, so neighbouring
+// regions are easy to tell apart
+fn write_pgm(grid: &Grid, labels: &[Option<u32>], out: &mut Write) -> io::Result<()> {
+    writeln!(out, "P2\n{} {}\n255", grid.width, grid.height)?;
+    for row in labels.chunks(grid.width) {
+        let shades: Vec<String> = row.iter().map(|l| l.map_or(0, |l| 64 + (l * 37) % 192).to_string()).collect();
+        writeln!(out, "{}", shades.join(" "))?;
+    }
+    Ok(())
+}
+
+// the grid as # and ., then the same grid with each used square replaced by its region ID
+fn write_text(grid: &Grid, labels: &[Option<u32>], regions: u32, out: &mut Write) -> io::Result<()> {
+    for y in 0..grid.height as i32 {
+        let row: String = (0..grid.width as i32).map(|x| if grid.nodes.contains(&(x, y)) { '#' } else { '.' }).collect();
+        writeln!(out, "{}", row)?;
+    }
+    writeln!(out)?;
+    let width = regions.saturating_sub(1).to_string().len();
+    for row in labels.chunks(grid.width) {
+        let cells: Vec<String> = row.iter().map(|l| match *l {
+            Some(l) => format!("{:>1$}", l, width),
+            None => format!("{:>1$}", ".", width),
+        }).collect();
+        writeln!(out, "{}", cells.join(" "))?;
     }
-    
+    Ok(())
 }
 
 fn bit_set(input: u8, n: usize) -> bool {
     input & (1 << n) != 0
 }
 
-fn main() {
-    let prefix = "hxtvlmkl";
-    let mut grid = Grid { nodes: HashSet::new() };
-    for n in 0..128 {
-        let mut input: Vec<u8> = format!("{}-{}", prefix, n).into();
+fn export<F>(args: &[String], flag: &str, write: F) where F: Fn(&mut Write) -> io::Result<()> {
+    if let Some(i) = args.iter().position(|a| a == flag) {
+        let path = args.get(i + 1).unwrap_or_else(|| panic!("{} needs a file.", flag));
+        let mut out = BufWriter::new(File::create(path).expect("Couldn't create output file."));
+        write(&mut out).expect("Couldn't write output file.");
+        println!("Wrote {}.", path);
+    }
+}
+
+// Rows are hashed in parallel, but collected back in order, so the grid (and everything
+// derived from it) is the same whatever the thread count. Squares have i32 coordinates, so
+// rows can't be more than i32::MAX.
+fn build_grid(key: &str, rows: usize) -> Grid {
+    let hashes: Vec<Vec<u8>> = (0..rows).into_par_iter().map(|n| {
+        let mut input: Vec<u8> = format!("{}-{}", key, n).into();
         let mut suffix = vec![17u8, 31u8, 73u8, 47u8, 23u8];
         input.append(&mut suffix);
-
-        let hash = hashify(&input, 64);   
+        hashify(&input, 64)
+    }).collect();
+    let mut grid = Grid { nodes: HashSet::new(), width: 128, height: rows };
+    for (n, hash) in hashes.iter().enumerate() {
         for (i, byte) in hash.iter().enumerate() {
             for j in 0..8 {
                 if bit_set(*byte, 7 - j) {
-                    grid.nodes.insert(((i as i32) * 8 + (j as i32), n));
+                    grid.nodes.insert(((i as i32) * 8 + (j as i32), n as i32));
                 }
             }
         }
     }
-    let colors = color_all(&grid);
-    let regions = colors.values().unique().count();
+    grid
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let option = |name: &str| args.iter().position(|a| a == name).map(|i| args.get(i + 1).unwrap_or_else(|| panic!("{} needs a value.", name)));
+    let key = option("--key").map_or("hxtvlmkl", |k| k.as_str());
+    let rows = option("--rows").map_or(128, |r| r.parse().expect("--rows needs a non-negative number."));
+    if rows > i32::MAX as usize {
+        panic!("--rows can be at most {}.", i32::MAX);
+    }
+    if let Some(threads) = option("--threads") {
+        let threads = threads.parse().expect("--threads needs a number.");
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global().expect("Couldn't start thread pool.");
+    }
+    let grid = build_grid(key, rows);
+    let (labels, regions) = label_regions(&grid);
+    println!("Used squares: {}", grid.nodes.len());
     println!("Total is: {}", regions);
+    if args.iter().any(|a| a == "--histogram") {
+        println!("Regions by size:");
+        for (size, count) in size_histogram(&labels, regions) {
+            println!("{:>5}: {}", size, count);
+        }
+    }
+    export(&args, "--pbm", |out| write_pbm(&grid, out));
+    export(&args, "--pgm", |out| write_pgm(&grid, &labels, out));
+    export(&args, "--text", |out| write_text(&grid, &labels, regions, out));
 }
@@ -0,0 +1,107 @@
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+use std::fmt;
+use std::str::FromStr;
+use std::error::Error;
+
+// How register arithmetic behaves at the edges of the register type. Each operation returns
+// None if the result doesn't fit; only checked arithmetic ever does.
+pub trait Arithmetic {
+    type Value: Clone + Ord + fmt::Display + fmt::Debug;
+    fn value(n: i64) -> Self::Value;
+    fn to_i64(a: &Self::Value) -> Option<i64>;
+    fn add(a: &Self::Value, b: &Self::Value) -> Option<Self::Value>;
+    fn mul(a: &Self::Value, b: &Self::Value) -> Option<Self::Value>;
+    fn rem(a: &Self::Value, b: &Self::Value) -> Option<Self::Value>; // never called with b == 0
+    fn is_zero(a: &Self::Value) -> bool;
+}
+
+pub struct Checked;
+pub struct Wrapping;
+pub struct Saturating;
+pub struct Unbounded;
+
+impl Arithmetic for Checked {
+    type Value = i64;
+    fn value(n: i64) -> i64 { n }
+    fn add(a: &i64, b: &i64) -> Option<i64> { a.checked_add(*b) }
+    fn mul(a: &i64, b: &i64) -> Option<i64> { a.checked_mul(*b) }
+    fn rem(a: &i64, b: &i64) -> Option<i64> { Some(a.wrapping_rem(*b)) } // MIN % -1 is just 0
+    fn is_zero(a: &i64) -> bool { *a == 0 }
+    fn to_i64(a: &i64) -> Option<i64> { Some(*a) }
+}
+
+impl Arithmetic for Wrapping {
+    type Value = i64;
+    fn value(n: i64) -> i64 { n }
+    fn add(a: &i64, b: &i64) -> Option<i64> { Some(a.wrapping_add(*b)) }
+    fn mul(a: &i64, b: &i64) -> Option<i64> { Some(a.wrapping_mul(*b)) }
+    fn rem(a: &i64, b: &i64) -> Option<i64> { Some(a.wrapping_rem(*b)) }
+    fn is_zero(a: &i64) -> bool { *a == 0 }
+    fn to_i64(a: &i64) -> Option<i64> { Some(*a) }
+}
+
+impl Arithmetic for Saturating {
+    type Value = i64;
+    fn value(n: i64) -> i64 { n }
+    fn add(a: &i64, b: &i64) -> Option<i64> { Some(a.saturating_add(*b)) }
+    fn mul(a: &i64, b: &i64) -> Option<i64> { Some(a.saturating_mul(*b)) }
+    fn rem(a: &i64, b: &i64) -> Option<i64> { Some(a.wrapping_rem(*b)) }
+    fn is_zero(a: &i64) -> bool { *a == 0 }
+    fn to_i64(a: &i64) -> Option<i64> { Some(*a) }
+}
+
+impl Arithmetic for Unbounded {
+    type Value = BigInt;
+    fn value(n: i64) -> BigInt { BigInt::from(n) }
+    fn add(a: &BigInt, b: &BigInt) -> Option<BigInt> { Some(a + b) }
+    fn mul(a: &BigInt, b: &BigInt) -> Option<BigInt> { Some(a * b) }
+    fn rem(a: &BigInt, b: &BigInt) -> Option<BigInt> { Some(a % b) }
+    fn is_zero(a: &BigInt) -> bool { a.is_zero() }
+    fn to_i64(a: &BigInt) -> Option<i64> { a.to_i64() }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode { Checked, Wrapping, Saturating, Unbounded }
+
+impl FromStr for Mode {
+    type Err = Box<Error>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "checked" => Ok(Mode::Checked),
+            "wrapping" => Ok(Mode::Wrapping),
+            "saturating" => Ok(Mode::Saturating),
+            "bigint" => Ok(Mode::Unbounded),
+            _ => Err(From::from("Invalid arithmetic mode; expected checked, wrapping, saturating or bigint.")),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fault { Overflow, ModuloByZero }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArithmeticError {
+    pub pid: i64,
+    pub pc: i64,
+    pub register: char,
+    pub fault: Fault,
+}
+
+impl fmt::Display for ArithmeticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.fault {
+            Fault::Overflow => write!(f, "Program {}, line {}: register {} overflowed.", self.pid, self.pc + 1, self.register),
+            Fault::ModuloByZero => write!(f, "Program {}, line {}: register {} taken modulo zero.", self.pid, self.pc + 1, self.register),
+        }
+    }
+}
+
+impl Error for ArithmeticError {
+    fn description(&self) -> &str {
+        match self.fault {
+            Fault::Overflow => "register overflowed",
+            Fault::ModuloByZero => "modulo by zero",
+        }
+    }
+}
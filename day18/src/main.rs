@@ -1,5 +1,9 @@
 #![feature(io)]
-#![feature(entry_and_modify)]
+
+extern crate num_bigint;
+extern crate num_traits;
+
+mod arithmetic;
 
 use std::fs::File;
 use std::io::prelude::*;
@@ -8,6 +12,8 @@ use std::error::Error;
 use std::str::FromStr;
 use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::env;
+use arithmetic::{Arithmetic, ArithmeticError, Checked, Fault, Mode, Saturating, Unbounded, Wrapping};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct RegisterId(pub char);
@@ -67,21 +73,21 @@ impl FromStr for Instruction {
 }
 
 #[derive(Debug, Clone)]
-struct ProgramState {
+struct ProgramState<V> {
     pub pc: i64,
     pub pid: i64,
     pub terminated: bool,
     pub waiting: bool,
-    pub registers: HashMap<RegisterId, i64>,
-    pub outputs: VecDeque<i64>,
+    pub registers: HashMap<RegisterId, V>,
+    pub outputs: VecDeque<V>,
     pub total_outputs: u64,
 }
 
-impl ProgramState {
-    fn new(pid: i64) -> Self {
+impl<V> ProgramState<V> {
+    fn new<A: Arithmetic<Value=V>>(pid: i64) -> Self {
         let register_names = "abcdefghijklmnopqrstuvwxyz".chars();
-        let mut registers: HashMap<RegisterId, i64> = register_names.map(|n| (RegisterId(n), 0)).collect();
-        registers.insert(RegisterId('p'), pid);
+        let mut registers: HashMap<RegisterId, V> = register_names.map(|n| (RegisterId(n), A::value(0))).collect();
+        registers.insert(RegisterId('p'), A::value(pid));
         Self {
             pc: 0, 
             pid: pid,
@@ -97,47 +103,59 @@ impl ProgramState {
         !self.terminated && !self.waiting
     }
 
-    fn produce(&mut self, val: i64) {
+    fn produce(&mut self, val: V) {
         self.outputs.push_back(val);
         self.total_outputs += 1;
     }
 
-    fn consume(&mut self) -> Option<i64> {
+    fn consume(&mut self) -> Option<V> {
         self.outputs.pop_front()
     }
 }
 
-fn value_of(registers: &HashMap<RegisterId, i64>, val: Value) -> i64 {
+fn value_of<A: Arithmetic>(registers: &HashMap<RegisterId, A::Value>, val: Value) -> A::Value {
     match val {
-        Value::Integer(n) => n,
-        Value::Register(id) => *registers.get(&id).unwrap_or(&0),
+        Value::Integer(n) => A::value(n),
+        Value::Register(id) => registers.get(&id).cloned().unwrap_or_else(|| A::value(0)),
+    }
+}
+
+fn update<A, F>(myself: &mut ProgramState<A::Value>, reg: RegisterId, f: F) -> Result<(), ArithmeticError>
+    where A: Arithmetic, F: FnOnce(&A::Value) -> Result<A::Value, Fault>
+{
+    let (pid, pc) = (myself.pid, myself.pc);
+    if let Some(x) = myself.registers.get_mut(&reg) {
+        *x = f(x).map_err(|fault| ArithmeticError { pid, pc, register: reg.0, fault })?;
     }
+    Ok(())
 }
 
-fn execute(myself: &mut ProgramState, other: &mut ProgramState, instr: Instruction) {
+fn execute<A: Arithmetic>(myself: &mut ProgramState<A::Value>, other: &mut ProgramState<A::Value>, instr: Instruction) -> Result<(), ArithmeticError> {
     match instr {
-        Instruction::Set(reg, val) => { 
-            let n = value_of(&myself.registers, val);
-            myself.registers.entry(reg).and_modify(|x| { *x = n; });
+        Instruction::Set(reg, val) => {
+            let n = value_of::<A>(&myself.registers, val);
+            update::<A, _>(myself, reg, |_| Ok(n))?;
             myself.pc += 1;
         }
-        Instruction::Add(reg, val) => { 
-            let n = value_of(&myself.registers, val);
-            myself.registers.entry(reg).and_modify(|x| { *x += n; });
+        Instruction::Add(reg, val) => {
+            let n = value_of::<A>(&myself.registers, val);
+            update::<A, _>(myself, reg, |x| A::add(x, &n).ok_or(Fault::Overflow))?;
             myself.pc += 1;
         }
         Instruction::Mul(reg, val) => {
-            let n = value_of(&myself.registers, val);
-            myself.registers.entry(reg).and_modify(|x| { *x *= n; });
+            let n = value_of::<A>(&myself.registers, val);
+            update::<A, _>(myself, reg, |x| A::mul(x, &n).ok_or(Fault::Overflow))?;
             myself.pc += 1;
         }
         Instruction::Mod(reg, val) => {
-            let n = value_of(&myself.registers, val);
-            myself.registers.entry(reg).and_modify(|x| { *x %= n; });
+            let n = value_of::<A>(&myself.registers, val);
+            update::<A, _>(myself, reg, |x| {
+                if A::is_zero(&n) { Err(Fault::ModuloByZero) } else { A::rem(x, &n).ok_or(Fault::Overflow) }
+            })?;
             myself.pc += 1;
         }
         Instruction::Snd(val) => {
-            let n = value_of(&myself.registers, val);
+            let n = value_of::<A>(&myself.registers, val);
             myself.produce(n);
             other.waiting = false;
             myself.pc += 1;
@@ -147,45 +165,73 @@ fn execute(myself: &mut ProgramState, other: &mut ProgramState, instr: Instructi
                 None => {
                     myself.waiting = true;
                 },
-                Some(n) => { 
-                    myself.registers.entry(reg).and_modify(|x| { *x = n; });
+                Some(n) => {
+                    update::<A, _>(myself, reg, |_| Ok(n))?;
                     myself.pc += 1;
                 }
             }
         }
         Instruction::Jgz(test, offset) => {
-            if value_of(&myself.registers, test) > 0 {
-                myself.pc += value_of(&myself.registers, offset);            
+            if value_of::<A>(&myself.registers, test) > A::value(0) {
+                // an offset too big for the program counter jumps clean out of the program
+                let offset = value_of::<A>(&myself.registers, offset);
+                match A::to_i64(&offset).and_then(|n| myself.pc.checked_add(n)) {
+                    Some(pc) => { myself.pc = pc; }
+                    None => { myself.terminated = true; }
+                }
             } else {
                 myself.pc += 1;
             }
         }
     }
+    Ok(())
 }
 
-fn main() {
-    let reader = BufReader::new(File::open("input").expect("Couldn't read input file."));
-    let lines = reader.lines().map(|x| x.expect("Couldn't read line."));
-    let instructions: Vec<Instruction> = lines.map(|x| x.trim().parse().expect("Couldn't parse instruction.")).collect();
+type Programs<V> = (ProgramState<V>, ProgramState<V>);
 
-    let mut x_state = ProgramState::new(0);
-    let mut y_state = ProgramState::new(1);
+fn simulate<A: Arithmetic>(instructions: &[Instruction]) -> Result<Programs<A::Value>, ArithmeticError> {
+    let mut x_state = ProgramState::new::<A>(0);
+    let mut y_state = ProgramState::new::<A>(1);
     while x_state.running() || y_state.running() {
         while x_state.running() {
             let instr = instructions.get(x_state.pc as usize).unwrap();
-            execute(&mut x_state, &mut y_state, *instr);
+            execute::<A>(&mut x_state, &mut y_state, *instr)?;
             if x_state.pc < 0 || (x_state.pc as usize) >= instructions.len() {
                 x_state.terminated = true;
             }
         }
         while y_state.running() {
             let instr = instructions.get(y_state.pc as usize).unwrap();
-            execute(&mut y_state, &mut x_state, *instr);
+            execute::<A>(&mut y_state, &mut x_state, *instr)?;
             if y_state.pc < 0 || (y_state.pc as usize) >= instructions.len() {
                 y_state.terminated = true;
             }
         }
     }
-    
-    println!("State at termination: {:?} {:?}", x_state, y_state);
+    Ok((x_state, y_state))
+}
+
+fn report<A: Arithmetic>(instructions: &[Instruction]) {
+    match simulate::<A>(instructions) {
+        Ok((x_state, y_state)) => println!("State at termination: {:?} {:?}", x_state, y_state),
+        Err(e) => println!("Program failed: {}", e),
+    }
+}
+
+fn main() {
+    let reader = BufReader::new(File::open("input").expect("Couldn't read input file."));
+    let lines = reader.lines().map(|x| x.expect("Couldn't read line."));
+    let instructions: Vec<Instruction> = lines.map(|x| x.trim().parse().expect("Couldn't parse instruction.")).collect();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mode = match args.iter().position(|a| a == "--arithmetic") {
+        Some(i) => args.get(i + 1).expect("--arithmetic needs a mode.").parse().expect("Couldn't parse arithmetic mode."),
+        None => Mode::Checked,
+    };
+    match mode {
+        Mode::Checked => report::<Checked>(&instructions),
+        Mode::Wrapping => report::<Wrapping>(&instructions),
+        Mode::Saturating => report::<Saturating>(&instructions),
+        Mode::Unbounded => report::<Unbounded>(&instructions),
+    }
 }
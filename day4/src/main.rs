@@ -6,20 +6,244 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::iter::FromIterator;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
+use std::fmt;
+use std::env;
 use itertools::Itertools;
 
 fn key(word: &str) -> String {
     String::from_iter(word.chars().sorted())
 }
 
-fn is_valid(passphrase: &str) -> bool {
-    let keys: Vec<String> = passphrase.split_whitespace().map(key).collect();
-    keys.len() == keys.iter().unique().count()
+// anagrams share a signature; lowercase ASCII words get letter counts without allocating,
+// and anything else (or a letter repeated more than 255 times) falls back to sorting
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Signature {
+    Counts([u8; 26]),
+    Sorted(String),
+}
+
+fn signature(word: &str) -> Signature {
+    let mut counts = [0u8; 26];
+    for b in word.bytes() {
+        match b {
+            b if b >= b'a' && b <= b'z' && counts[(b - b'a') as usize] < ::std::u8::MAX => { counts[(b - b'a') as usize] += 1; }
+            _ => { return Signature::Sorted(key(word)); }
+        }
+    }
+    Signature::Counts(counts)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rule {
+    NoDuplicateWords,
+    NoAnagrams,
+    MinWordCount(usize),
+    AllowedAlphabet(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Violation<'a> {
+    DuplicateWord(&'a str, &'a str),
+    Anagram(&'a str, &'a str),
+    TooFewWords(usize, usize),
+    DisallowedCharacter(&'a str, char),
+}
+
+impl<'a> fmt::Display for Violation<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Violation::DuplicateWord(a, b) => write!(f, "no duplicate words: '{}' repeats '{}'", b, a),
+            Violation::Anagram(a, b) => write!(f, "no anagrams: '{}' is an anagram of '{}'", b, a),
+            Violation::TooFewWords(n, min) => write!(f, "minimum word count: {} words, needed {}", n, min),
+            Violation::DisallowedCharacter(w, ch) => write!(f, "allowed alphabet: '{}' contains '{}'", w, ch),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PassphrasePolicy {
+    pub rules: Vec<Rule>,
+    pub case_insensitive: bool,
+}
+
+impl PassphrasePolicy {
+    fn new(rules: Vec<Rule>) -> Self {
+        Self { rules, case_insensitive: false }
+    }
+
+    fn case_insensitive(mut self) -> Self {
+        self.case_insensitive = true;
+        self
+    }
+
+    fn normalize<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        if self.case_insensitive { Cow::Owned(word.to_lowercase()) } else { Cow::Borrowed(word) }
+    }
+
+    // every pair of words in the passphrase whose keys collide, in order of appearance
+    fn collisions<'a, K, F>(&self, words: &[&'a str], key_fn: F) -> Vec<(&'a str, &'a str)>
+        where K: Hash + Eq, F: Fn(Cow<'a, str>) -> K
+    {
+        let mut seen: HashMap<K, &'a str> = HashMap::with_capacity(words.len());
+        let mut result = Vec::new();
+        for word in words {
+            let k = key_fn(self.normalize(word));
+            match seen.get(&k) {
+                Some(prev) => { result.push((*prev, *word)); }
+                None => { seen.insert(k, *word); }
+            }
+        }
+        result
+    }
+
+    fn check<'a>(&self, passphrase: &'a str) -> Vec<Violation<'a>> {
+        let words: Vec<&str> = passphrase.split_whitespace().collect();
+        let mut violations = Vec::new();
+        for rule in &self.rules {
+            match *rule {
+                Rule::NoDuplicateWords => {
+                    let pairs = self.collisions(&words, |w| w);
+                    violations.extend(pairs.into_iter().map(|(a, b)| Violation::DuplicateWord(a, b)));
+                }
+                Rule::NoAnagrams => {
+                    // a word repeated as is is NoDuplicateWords' business, not an anagram
+                    let pairs = self.collisions(&words, |w| signature(&w));
+                    let pairs = pairs.into_iter().filter(|&(a, b)| self.normalize(a) != self.normalize(b));
+                    violations.extend(pairs.map(|(a, b)| Violation::Anagram(a, b)));
+                }
+                Rule::MinWordCount(min) => {
+                    if words.len() < min {
+                        violations.push(Violation::TooFewWords(words.len(), min));
+                    }
+                }
+                Rule::AllowedAlphabet(ref alphabet) => {
+                    let alphabet = self.normalize(alphabet);
+                    for word in &words {
+                        let normalized = self.normalize(word);
+                        if let Some(ch) = normalized.chars().find(|ch| !alphabet.contains(*ch)) {
+                            violations.push(Violation::DisallowedCharacter(word, ch));
+                        }
+                    }
+                }
+            }
+        }
+        violations
+    }
+
+    fn is_valid(&self, passphrase: &str) -> bool {
+        self.check(passphrase).is_empty()
+    }
+}
+
+fn report(policy: &PassphrasePolicy, passphrases: &[String]) {
+    for (i, passphrase) in passphrases.iter().enumerate() {
+        let violations = policy.check(passphrase);
+        if !violations.is_empty() {
+            println!("Line {}: {}", i + 1, passphrase);
+            for violation in violations {
+                println!("    {}", violation);
+            }
+        }
+    }
+}
+
+// xorshift32, so every --bench run scores the same passphrases
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+// four to eleven words of two to seven letters from a-h, so anagrams turn up now and then
+fn generate(rng: &mut Rng, lines: usize) -> Vec<String> {
+    let mut passphrases = Vec::with_capacity(lines);
+    for _ in 0..lines {
+        let word_count = 4 + rng.next() % 8;
+        let words: Vec<String> = (0..word_count).map(|_| {
+            let length = 2 + rng.next() % 6;
+            (0..length).map(|_| (b'a' + (rng.next() % 8) as u8) as char).collect()
+        }).collect();
+        passphrases.push(words.join(" "));
+    }
+    passphrases
+}
+
+fn seconds(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9
+}
+
+fn bench(lines: usize) {
+    let passphrases = generate(&mut Rng(2463534242), lines);
+
+    let start = Instant::now();
+    let sorted_valid = passphrases.iter().filter(|p| {
+        let keys: Vec<String> = p.split_whitespace().map(key).collect();
+        keys.len() == keys.iter().unique().count()
+    }).count();
+    let sorted_time = seconds(start.elapsed());
+
+    let start = Instant::now();
+    let signature_valid = passphrases.iter().filter(|p| {
+        let signatures: Vec<Signature> = p.split_whitespace().map(signature).collect();
+        signatures.len() == signatures.iter().unique().count()
+    }).count();
+    let signature_time = seconds(start.elapsed());
+
+    assert_eq!(sorted_valid, signature_valid, "Sorted keys and signatures disagree!");
+    println!("Generated {} passphrases, {} valid.", lines, signature_valid);
+    println!("Sorted keys: {:.3}s ({:.0} lines/s)", sorted_time, lines as f64 / sorted_time);
+    println!("Signatures: {:.3}s ({:.0} lines/s)", signature_time, lines as f64 / signature_time);
 }
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|a| a.as_str()) == Some("--bench") {
+        let lines = args.get(1).map(|n| n.parse().expect("--bench needs a number.")).unwrap_or(1_000_000);
+        return bench(lines);
+    }
+
     let reader = BufReader::new(File::open("input").expect("Couldn't read input file."));
-    let all_passphrases = reader.lines().filter_map(|l| l.ok());
-    let valid_passphrases = all_passphrases.filter(|p| is_valid(p));
-    println!("Number of valid passphrases: {}", valid_passphrases.count());
+    let all_passphrases: Vec<String> = reader.lines().filter_map(|l| l.ok()).collect();
+
+    // extra rules from the command line apply on top of both parts' rules
+    let mut extra_rules = Vec::new();
+    let mut case_insensitive = false;
+    let mut show_report = false;
+    let mut args_iter = args.iter();
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--report" => { show_report = true; }
+            "--case-insensitive" => { case_insensitive = true; }
+            "--min-words" => {
+                let n = args_iter.next().and_then(|n| n.parse().ok()).expect("--min-words needs a number.");
+                extra_rules.push(Rule::MinWordCount(n));
+            }
+            "--alphabet" => {
+                let alphabet = args_iter.next().expect("--alphabet needs a set of characters.");
+                extra_rules.push(Rule::AllowedAlphabet(alphabet.clone()));
+            }
+            _ => panic!("Unrecognized argument: {}", arg),
+        }
+    }
+
+    let mut part1 = PassphrasePolicy::new([vec![Rule::NoDuplicateWords], extra_rules.clone()].concat());
+    let mut part2 = PassphrasePolicy::new([vec![Rule::NoDuplicateWords, Rule::NoAnagrams], extra_rules].concat());
+    if case_insensitive {
+        part1 = part1.case_insensitive();
+        part2 = part2.case_insensitive();
+    }
+    if show_report {
+        report(&part2, &all_passphrases);
+    }
+    println!("Number of valid passphrases (no duplicates): {}", all_passphrases.iter().filter(|p| part1.is_valid(p)).count());
+    println!("Number of valid passphrases (no anagrams): {}", all_passphrases.iter().filter(|p| part2.is_valid(p)).count());
 }
@@ -3,36 +3,225 @@
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::env;
+use std::time::{Duration, Instant};
 
-fn step(instructions: &mut Vec<i32>, at: usize) -> Option<usize> {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Exit {
+    Start, // jumped to a negative offset
+    End, // jumped past the last offset
+}
+
+#[derive(Debug, Clone)]
+struct Outcome {
+    pub steps: u64,
+    pub exit: Exit,
+    pub offsets: Vec<i32>,
+    pub visits: Vec<u64>,
+}
+
+fn always_increment(_instr: i32) -> i32 {
+    1
+}
+
+fn strange_jumps(instr: i32) -> i32 {
+    if instr >= 3 { -1 } else { 1 }
+}
+
+fn step<F>(instructions: &mut [i32], at: usize, update: &F) -> Result<usize, Exit> where F: Fn(i32) -> i32 {
     let instr = instructions[at];
-    let next = at as i32 + instr;
-    let delta = if instr >= 3 { -1 } else { 1 };
-    instructions[at] = instr + delta;
-    if next >= 0 && (next as usize) < instructions.len() {
-        Some(next as usize)
+    let next = at as i64 + instr as i64;
+    instructions[at] = instr + update(instr);
+    if next < 0 {
+        Err(Exit::Start)
+    } else if next as usize >= instructions.len() {
+        Err(Exit::End)
     } else {
-        None
+        Ok(next as usize)
     }
 }
 
-fn execute(instructions: &mut Vec<i32>) -> u32 {
+fn execute<F>(mut instructions: Vec<i32>, update: F) -> Outcome where F: Fn(i32) -> i32 {
+    let mut visits = vec![0; instructions.len()];
     let mut steps = 0;
     let mut curr = 0;
     loop {
         steps += 1;
-        if let Some(next) = step(instructions, curr) {
-            curr = next; 
+        visits[curr] += 1;
+        match step(&mut instructions, curr, &update) {
+            Ok(next) => { curr = next; }
+            Err(exit) => { return Outcome { steps, exit, offsets: instructions, visits }; }
+        }
+    }
+}
+
+// Under strange_jumps a cell holding 2 or 3 flips between the two forever and always jumps
+// forwards, so once a prefix of the maze has settled we pack it into 16-cell blocks (one bit
+// per cell, set for 3) and cross each block with a single lookup in a precomputed table.
+const BLOCK_SIZE: usize = 16;
+
+#[derive(Debug, Copy, Clone)]
+struct Transition {
+    pub state: u16, // block contents after the pointer leaves
+    pub steps: u8,
+    pub exit: u8, // pointer position relative to the block start, always past the block
+}
+
+// Indexed by entry * 2^BLOCK_SIZE + contents, so the entries the walk mostly uses (0, 1 and 2,
+// just past the previous block) share one contiguous part of the table.
+fn transitions() -> Vec<Transition> {
+    let mut table = Vec::with_capacity(BLOCK_SIZE << BLOCK_SIZE);
+    for entry in 0..BLOCK_SIZE {
+        for initial in 0..(1 << BLOCK_SIZE) {
+            let mut state = initial as u16;
+            let mut pos = entry;
+            let mut steps = 0;
+            while pos < BLOCK_SIZE {
+                let jump = 2 + ((state >> pos) & 1) as usize;
+                state ^= 1 << pos;
+                pos += jump;
+                steps += 1;
+            }
+            table.push(Transition { state, steps, exit: pos as u8 });
+        }
+    }
+    table
+}
+
+fn settled(instr: i32) -> bool {
+    instr == 2 || instr == 3
+}
+
+// equivalent to execute(instructions, strange_jumps), minus the visit histogram; table is
+// whatever transitions() returns, which is slow enough to build that it's worth reusing
+fn fast_forward(table: &[Transition], mut instructions: Vec<i32>) -> (u64, Exit, Vec<i32>) {
+    let mut blocks: Vec<u16> = Vec::new();
+    let mut settled_len = 0;
+    let mut steps = 0;
+    let mut curr = 0;
+    let exit = loop {
+        let mut block = curr / BLOCK_SIZE;
+        if block < blocks.len() {
+            // The pointer always leaves a block into the next one, so walk the blocks in order.
+            // Past the first block the entry is 0, 1 or 2; looking all three up before knowing
+            // which one applies means no lookup has to wait for the one before it.
+            let mut entry = curr % BLOCK_SIZE;
+            while block < blocks.len() {
+                let state = blocks[block] as usize;
+                let transition = if entry < 3 {
+                    let candidates = [table[state], table[1 << BLOCK_SIZE | state], table[2 << BLOCK_SIZE | state]];
+                    candidates[entry]
+                } else {
+                    table[entry << BLOCK_SIZE | state]
+                };
+                blocks[block] = transition.state;
+                steps += transition.steps as u64;
+                entry = transition.exit as usize - BLOCK_SIZE;
+                block += 1;
+            }
+            curr = block * BLOCK_SIZE + entry;
+            if curr >= instructions.len() {
+                break Exit::End;
+            }
         } else {
-            return steps;
+            steps += 1;
+            match step(&mut instructions, curr, &strange_jumps) {
+                Ok(next) => { curr = next; }
+                Err(exit) => { break exit; }
+            }
+            while settled_len < instructions.len() && settled(instructions[settled_len]) {
+                settled_len += 1;
+            }
+            while (blocks.len() + 1) * BLOCK_SIZE <= settled_len {
+                let start = blocks.len() * BLOCK_SIZE;
+                let cells = &instructions[start..start + BLOCK_SIZE];
+                blocks.push(cells.iter().enumerate().fold(0, |acc, (i, x)| acc | (((*x == 3) as u16) << i)));
+            }
+        }
+    };
+    for (block, state) in blocks.iter().enumerate() {
+        for i in 0..BLOCK_SIZE {
+            instructions[block * BLOCK_SIZE + i] = 2 + ((state >> i) & 1) as i32;
+        }
+    }
+    (steps, exit, instructions)
+}
+
+// xorshift32, so --bench times the same maze on every run
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+// shaped like the puzzle input: backwards jumps of up to a thousand or so offsets
+fn generate(rng: &mut Rng, length: usize) -> Vec<i32> {
+    (0..length).map(|i| 2 - (rng.next() % (i.min(1000) as u32 + 3)) as i32).collect()
+}
+
+fn seconds(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9
+}
+
+fn bench(length: usize) {
+    let instructions = generate(&mut Rng(2463534242), length);
+    let start = Instant::now();
+    let expected = execute(instructions.clone(), strange_jumps);
+    let naive_time = seconds(start.elapsed());
+    let start = Instant::now();
+    let (steps, exit, _) = fast_forward(&transitions(), instructions);
+    let fast_time = seconds(start.elapsed());
+    assert_eq!((expected.steps, expected.exit), (steps, exit), "Fast-forward disagrees with naive interpreter!");
+    println!("{} offsets, {} steps, exited off the {:?}.", length, steps, exit);
+    println!("Naive: {:.3}s, fast-forward: {:.3}s", naive_time, fast_time);
+}
+
+fn describe(name: &str, outcome: &Outcome, show_histogram: bool) {
+    println!("{}: {} steps, exited off the {:?}.", name, outcome.steps, outcome.exit);
+    if show_histogram {
+        for (i, (offset, visits)) in outcome.offsets.iter().zip(&outcome.visits).enumerate() {
+            println!("    {:>5}: offset {:>5}, visited {} times", i, offset, visits);
         }
     }
 }
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(|a| a.as_str()) == Some("--bench") {
+        return bench(args.get(1).map_or(20_000, |n| n.parse().expect("--bench needs a number.")));
+    }
+    let show_histogram = args.iter().any(|a| a == "--histogram");
     let reader = BufReader::new(File::open("input").expect("Couldn't read input file."));
     let lines = reader.lines().filter_map(|l| l.ok());
-    let mut instructions: Vec<i32> = lines.filter_map(|l| l.parse().ok()).collect();
-    let steps = execute(&mut instructions);
-    println!("Number of steps: {}", steps);
+    let instructions: Vec<i32> = lines.filter_map(|l| l.parse().ok()).collect();
+    describe("Always increment", &execute(instructions.clone(), always_increment), show_histogram);
+    if show_histogram {
+        describe("Strange jumps", &execute(instructions, strange_jumps), show_histogram);
+    } else {
+        let (steps, exit, _) = fast_forward(&transitions(), instructions);
+        println!("Strange jumps: {} steps, exited off the {:?}.", steps, exit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_forward_matches_execute() {
+        let table = transitions();
+        let mut rng = Rng(2463534242);
+        for trial in 0..200 {
+            let instructions = generate(&mut rng, 1 + (trial * 7) % 500);
+            let expected = execute(instructions.clone(), strange_jumps);
+            let (steps, exit, offsets) = fast_forward(&table, instructions);
+            assert_eq!((expected.steps, expected.exit), (steps, exit), "Mismatch on trial {}!", trial);
+            assert_eq!(expected.offsets, offsets, "Final offsets differ on trial {}!", trial);
+        }
+    }
 }
@@ -0,0 +1,33 @@
+// Brent's cycle detection: for the sequence initial, step(initial), step(step(initial)), ...
+// returns (mu, lambda), where mu is the index of the first state that repeats and lambda is
+// the length of the loop. Only a couple of states are held at any time.
+pub fn find_cycle<T, F>(initial: T, step: F) -> (usize, usize) where T: Clone + PartialEq, F: Fn(&T) -> T {
+    // find lambda by teleporting the tortoise to the hare at every power of two
+    let mut power = 1;
+    let mut lambda = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    while tortoise != hare {
+        if power == lambda {
+            tortoise = hare.clone();
+            power *= 2;
+            lambda = 0;
+        }
+        hare = step(&hare);
+        lambda += 1;
+    }
+
+    // then walk two pointers lambda apart from the start until they meet at mu
+    let mut mu = 0;
+    tortoise = initial.clone();
+    hare = initial;
+    for _ in 0..lambda {
+        hare = step(&hare);
+    }
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        mu += 1;
+    }
+    (mu, lambda)
+}
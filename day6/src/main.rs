@@ -1,37 +1,87 @@
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
+mod cycle;
 
-fn redistribute_one(banks: &mut Vec<u32>) {
+use cycle::find_cycle;
+
+fn redistribute_one(banks: &mut [u32]) {
     let count = banks.len();
     let (source_idx, source_blocks) = banks.iter()
         .enumerate()
-        .max_by_key(|&(i, n)| (n, -(i as i32))) // largest value, smallest index breaks ties
+        .max_by_key(|&(i, n)| (n, -(i as i64))) // largest value, smallest index breaks ties
         .map(|(i, n)| (i, *n))
         .unwrap();
-        
+
+    // every bank gets an equal share, then the remainder goes one apiece to the banks
+    // following the source
     banks[source_idx] = 0;
-    for i in 0..source_blocks {
-        banks[(source_idx + 1 + i as usize) % count] += 1;
+    let share = source_blocks / count as u32;
+    let remainder = source_blocks as usize % count;
+    for bank in banks.iter_mut() {
+        *bank += share;
+    }
+    for i in 0..remainder {
+        banks[(source_idx + 1 + i) % count] += 1;
     }
 }
 
-fn redistribute_all(banks: &mut Vec<u32>) -> u32 {
-    let mut seen: HashMap<Vec<u32>, u32> = HashMap::new();
-    for i in 0.. {
-        match seen.entry(banks.clone()) {
-            Entry::Occupied(e) => { return i - e.get(); }
-            Entry::Vacant(e) => { 
-                e.insert(i);
-                redistribute_one(banks);
-            }
-        }
-    }
-    unreachable!("The loop must terminate.");
+fn redistribute_all(banks: &[u32]) -> (usize, usize) {
+    find_cycle(banks.to_vec(), |banks| {
+        let mut next = banks.clone();
+        redistribute_one(&mut next);
+        next
+    })
 }
 
 fn main() {
     let input = include_str!("../input");
-    let mut banks: Vec<u32> = input.split_whitespace().filter_map(|l| l.parse().ok()).collect();
-    let steps = redistribute_all(&mut banks);
-    println!("Number of steps: {}", steps);
+    let banks: Vec<u32> = input.split_whitespace().filter_map(|l| l.parse().ok()).collect();
+    let (mu, lambda) = redistribute_all(&banks);
+    println!("Steps until a repeated state: {}", mu + lambda);
+    println!("Size of the loop: {}", lambda);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one block at a time, as the puzzle describes it
+    fn redistribute_one_naive(banks: &mut [u32]) {
+        let count = banks.len();
+        let (source_idx, source_blocks) = banks.iter()
+            .enumerate()
+            .max_by_key(|&(i, n)| (n, -(i as i64)))
+            .map(|(i, n)| (i, *n))
+            .unwrap();
+
+        banks[source_idx] = 0;
+        for i in 0..source_blocks {
+            banks[(source_idx + 1 + i as usize) % count] += 1;
+        }
+    }
+
+    // xorshift32, so every run checks the same banks
+    struct Rng(u32);
+
+    impl Rng {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+    }
+
+    #[test]
+    fn bulk_matches_naive() {
+        let mut rng = Rng(2463534242);
+        for trial in 0..1000 {
+            let count = 1 + trial % 20;
+            let mut banks: Vec<u32> = (0..count).map(|_| rng.next() % 51).collect();
+            let mut expected = banks.clone();
+            for round in 0..100 {
+                redistribute_one(&mut banks);
+                redistribute_one_naive(&mut expected);
+                assert_eq!(banks, expected, "Mismatch on trial {} after {} rounds!", trial, round + 1);
+            }
+        }
+    }
 }
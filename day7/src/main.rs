@@ -9,7 +9,10 @@ use std::io::prelude::*;
 use std::io::BufReader;
 use regex::Regex;
 use std::error::Error;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::Write;
+use std::env;
 
 lazy_static! {
     static ref LINE_RE: Regex = Regex::new(
@@ -17,7 +20,7 @@ lazy_static! {
     ).unwrap();
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Id<'a>(pub &'a str);
 
 #[derive(Debug, Copy, Clone)]
@@ -28,52 +31,277 @@ struct Node<'a> {
 
 #[derive(Debug, Clone)]
 struct Tree<'a> {
+    pub root: Id<'a>,
     pub nodes: HashMap<Id<'a>, Node<'a>>,
     pub edges: HashMap<Id<'a>, Vec<Id<'a>>>,
 }
 
-fn total_weight<'a>(tree: &'a Tree, root: Id<'a>) -> u32 {
-    let node = tree.nodes.get(&root).unwrap();
-    match tree.edges.get(&root) {
-        None => node.weight,
-        Some(xs) => node.weight + xs.iter().map(|x| { total_weight(tree, *x) }).sum::<u32>()
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TreeError<'a> {
+    Empty,
+    DuplicateProgram(Id<'a>),
+    DanglingChild { parent: Id<'a>, child: Id<'a> },
+    MultipleParents { child: Id<'a>, parents: (Id<'a>, Id<'a>) },
+    MultipleRoots(Vec<Id<'a>>),
+    Cycle(Vec<Id<'a>>),
+}
+
+impl<'a> fmt::Display for TreeError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TreeError::Empty => write!(f, "there are no programs in the tower"),
+            TreeError::DuplicateProgram(id) => write!(f, "program {} is defined more than once", id.0),
+            TreeError::DanglingChild { parent, child } => write!(f, "{} holds {}, which is never defined", parent.0, child.0),
+            TreeError::MultipleParents { child, parents: (a, b) } => write!(f, "{} is held by both {} and {}", child.0, a.0, b.0),
+            TreeError::MultipleRoots(ref roots) => {
+                let names: Vec<&str> = roots.iter().map(|id| id.0).collect();
+                write!(f, "no single bottom program; candidates are {}", names.join(", "))
+            }
+            TreeError::Cycle(ref ids) => {
+                let names: Vec<&str> = ids.iter().map(|id| id.0).collect();
+                write!(f, "programs hold each other in a cycle: {} -> {}", names.join(" -> "), names[0])
+            }
+        }
     }
 }
 
-fn weigh_subtrees<'a>(tree: &'a Tree, subtrees: &'a Vec<Id>) -> HashMap<u32, Vec<Id<'a>>> {
-    let mut weights: HashMap<u32, Vec<Id>> = HashMap::new(); // {subtree_weight: [subtree]}
-    for subtree in subtrees {
-        weights.entry(total_weight(tree, *subtree)).or_insert_with(Vec::new).push(*subtree);
+impl<'a> Tree<'a> {
+    fn new<I>(entries: I) -> Result<Self, TreeError<'a>> where I: IntoIterator<Item=(Node<'a>, Vec<Id<'a>>)> {
+        let mut nodes = HashMap::new();
+        let mut edges = HashMap::new();
+        let mut order = Vec::new(); // ids in input order, so the same bad input always gets the same error
+        for (program, links) in entries {
+            if nodes.insert(program.id, program).is_some() {
+                return Err(TreeError::DuplicateProgram(program.id));
+            }
+            edges.insert(program.id, links);
+            order.push(program.id);
+        }
+        if nodes.is_empty() {
+            return Err(TreeError::Empty);
+        }
+
+        // every program but the root is held by exactly one other
+        let mut parents: HashMap<Id, Id> = HashMap::new();
+        for parent in &order {
+            for child in &edges[parent] {
+                if !nodes.contains_key(child) {
+                    return Err(TreeError::DanglingChild { parent: *parent, child: *child });
+                }
+                if let Some(other) = parents.insert(*child, *parent) {
+                    let (a, b) = if other < *parent { (other, *parent) } else { (*parent, other) };
+                    return Err(TreeError::MultipleParents { child: *child, parents: (a, b) });
+                }
+            }
+        }
+
+        let mut roots: Vec<Id> = nodes.keys().filter(|id| !parents.contains_key(id)).cloned().collect();
+        roots.sort();
+        if roots.len() > 1 {
+            return Err(TreeError::MultipleRoots(roots));
+        }
+
+        // anything the root can't reach only has parents, so following them must loop
+        let reachable = match roots.first() {
+            Some(root) => reachable_from(&edges, *root),
+            None => HashSet::new(),
+        };
+        let mut unreachable: Vec<Id> = nodes.keys().filter(|id| !reachable.contains(id)).cloned().collect();
+        unreachable.sort();
+        if let Some(start) = unreachable.first() {
+            return Err(TreeError::Cycle(cycle_through_parents(&parents, *start)));
+        }
+
+        Ok(Tree { root: roots[0], nodes, edges })
+    }
+}
+
+fn reachable_from<'a>(edges: &HashMap<Id<'a>, Vec<Id<'a>>>, root: Id<'a>) -> HashSet<Id<'a>> {
+    let mut seen = HashSet::new();
+    let mut pending = vec![root];
+    while let Some(id) = pending.pop() {
+        if seen.insert(id) {
+            if let Some(children) = edges.get(&id) {
+                pending.extend(children.iter().cloned());
+            }
+        }
+    }
+    seen
+}
+
+fn cycle_through_parents<'a>(parents: &HashMap<Id<'a>, Id<'a>>, start: Id<'a>) -> Vec<Id<'a>> {
+    let mut path = vec![start];
+    let mut curr = start;
+    loop {
+        curr = parents[&curr];
+        if let Some(i) = path.iter().position(|id| *id == curr) {
+            let mut cycle = path.split_off(i);
+            cycle.reverse(); // parent before child
+            return cycle;
+        }
+        path.push(curr);
+    }
+}
+
+// subtree weight of every program, computed bottom-up in a single post-order pass
+fn subtree_weights<'a>(tree: &Tree<'a>) -> HashMap<Id<'a>, u32> {
+    let mut weights = HashMap::with_capacity(tree.nodes.len());
+    let mut pending = vec![(tree.root, false)];
+    while let Some((id, children_done)) = pending.pop() {
+        let children = tree.children(id);
+        if children_done {
+            let total = tree.nodes[&id].weight + children.iter().map(|x| weights[x]).sum::<u32>();
+            weights.insert(id, total);
+        } else {
+            pending.push((id, true));
+            pending.extend(children.iter().map(|x| (*x, false)));
+        }
     }
     weights
 }
 
-fn find_unbalanced<'a>(tree: &'a Tree, root: Id<'a>) -> (Id<'a>, u32) { 
-    // find the single subtree with a different weight, then call find_unbalanced_in_subtree
-    // on it with the weight we know it's supposed to have
-    let subtrees = tree.edges.get(&root).expect("Root must have children!");
-    let weights = weigh_subtrees(tree, subtrees);
-    let (_, subtree) = weights.iter().find(|&(_k, v)| v.len() == 1).expect("Whole tree is balanced!");
-    let (target_weight, _) = weights.iter().find(|&(_k, v)| v.len() > 1).expect("No unique solution!");
-    find_unbalanced_in_subtree(tree, subtree[0], *target_weight)
-}
-
-fn find_unbalanced_in_subtree<'a>(tree: &'a Tree, root: Id<'a>, target_weight: u32) -> (Id<'a>, u32) {
-    match tree.edges.get(&root) {
-        None => (root, target_weight), // no subtrees; this node needs the right weight
-        Some(subtrees) => {
-            // does one of the subtrees have a different weight than the others?
-            let weights = weigh_subtrees(tree, subtrees);
-            let total_subtree_weights: u32 = weights.iter().map(|(k, v)| *k * v.len() as u32).sum();
-            match weights.iter().find(|&(_k, v)| v.len() == 1) {
-                None => (root, target_weight - total_subtree_weights), // all subtrees have same weight; must be this node's fault
-                Some((_, bad_subtree)) => {
-                    let (target_weight, _) = weights.iter().find(|&(_k, v)| v.len() > 1).expect("No unique solution!");
-                    find_unbalanced_in_subtree(tree, bad_subtree[0], *target_weight)
+fn weigh_subtrees<'a>(weights: &HashMap<Id<'a>, u32>, subtrees: &[Id<'a>]) -> HashMap<u32, Vec<Id<'a>>> {
+    let mut groups: HashMap<u32, Vec<Id>> = HashMap::new(); // {subtree_weight: [subtree]}
+    for subtree in subtrees {
+        groups.entry(weights[subtree]).or_insert_with(Vec::new).push(*subtree);
+    }
+    groups
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Resolution<'a> {
+    Outlier { child: Id<'a>, target_weight: u32 }, // this subtree should weigh target_weight instead
+    Ambiguous(Vec<Id<'a>>), // any of these subtrees could be the wrong one
+}
+
+#[derive(Debug, Clone)]
+struct Imbalance<'a> {
+    pub node: Id<'a>,
+    pub children: Vec<(Id<'a>, u32)>,
+    pub resolution: Resolution<'a>,
+}
+
+fn resolve<'a>(groups: &HashMap<u32, Vec<Id<'a>>>) -> Resolution<'a> {
+    let singles: Vec<(u32, Id)> = groups.iter().filter(|&(_k, v)| v.len() == 1).map(|(k, v)| (*k, v[0])).collect();
+    let majorities: Vec<u32> = groups.iter().filter(|&(_k, v)| v.len() > 1).map(|(k, _v)| *k).collect();
+    if groups.len() == 2 && singles.len() == 1 && majorities.len() == 1 {
+        Resolution::Outlier { child: singles[0].1, target_weight: majorities[0] }
+    } else {
+        let mut candidates: Vec<Id> = if majorities.len() == 1 {
+            singles.iter().map(|&(_k, id)| id).collect()
+        } else {
+            groups.values().flat_map(|v| v.iter().cloned()).collect()
+        };
+        candidates.sort();
+        Resolution::Ambiguous(candidates)
+    }
+}
+
+// every program whose children's subtrees don't all weigh the same, from the root upwards
+fn find_imbalances<'a>(tree: &Tree<'a>, weights: &HashMap<Id<'a>, u32>) -> Vec<Imbalance<'a>> {
+    let mut imbalances = Vec::new();
+    let mut pending = vec![tree.root];
+    while let Some(id) = pending.pop() {
+        let children = tree.children(id);
+        pending.extend(children.iter().cloned());
+        let groups = weigh_subtrees(weights, children);
+        if groups.len() > 1 {
+            imbalances.push(Imbalance {
+                node: id,
+                children: children.iter().map(|x| (*x, weights[x])).collect(),
+                resolution: resolve(&groups),
+            });
+        }
+    }
+
+    // with only one wrong weight in the tower, an ambiguous choice is settled if just one of
+    // the candidates is unbalanced itself and the remaining children agree with each other
+    let unbalanced: HashSet<Id> = imbalances.iter().map(|i| i.node).collect();
+    for imbalance in &mut imbalances {
+        let resolution = match imbalance.resolution {
+            Resolution::Ambiguous(ref candidates) => {
+                let suspects: Vec<Id> = candidates.iter().filter(|id| unbalanced.contains(id)).cloned().collect();
+                let others: HashSet<u32> = imbalance.children.iter().filter(|c| !suspects.contains(&c.0)).map(|c| c.1).collect();
+                match (suspects.len(), others.len()) {
+                    (1, 1) => Some(Resolution::Outlier { child: suspects[0], target_weight: *others.iter().next().unwrap() }),
+                    _ => None,
                 }
             }
+            Resolution::Outlier { .. } => None,
+        };
+        if let Some(resolution) = resolution {
+            imbalance.resolution = resolution;
         }
     }
+    imbalances
+}
+
+fn find_unbalanced<'a>(tree: &Tree<'a>, imbalances: &[Imbalance<'a>], weights: &HashMap<Id<'a>, u32>) -> Result<(Id<'a>, u32), Box<Error>> {
+    // follow the outliers down from the root; the last one is balanced above, so its own weight is wrong
+    let mut curr = imbalances.iter().find(|i| i.node == tree.root).ok_or("Whole tree is balanced!")?;
+    loop {
+        match curr.resolution {
+            Resolution::Ambiguous(_) => { return Err(From::from(format!("No unique solution below {}!", curr.node.0))); }
+            Resolution::Outlier { child, target_weight } => match imbalances.iter().find(|i| i.node == child) {
+                Some(next) => { curr = next; }
+                None => {
+                    let corrected = tree.nodes[&child].weight as i64 + target_weight as i64 - weights[&child] as i64;
+                    if corrected < 0 {
+                        return Err(From::from(format!("{} would need a negative weight!", child.0)));
+                    }
+                    return Ok((child, corrected as u32));
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Tree<'a> {
+    fn children(&self, id: Id<'a>) -> &[Id<'a>] {
+        self.edges.get(&id).map_or(&[][..], |xs| &xs[..])
+    }
+
+    // programs holding unbalanced subtrees are outlined, the wrong weight is filled in
+    fn to_dot(&self, weights: &HashMap<Id<'a>, u32>, unbalanced: &HashSet<Id<'a>>, culprit: Option<Id<'a>>) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph tower {{").unwrap();
+        writeln!(out, "    node [shape=box];").unwrap();
+        let mut ids: Vec<&Id> = self.nodes.keys().collect();
+        ids.sort();
+        for id in ids {
+            let style = if Some(*id) == culprit {
+                ", style=filled, fillcolor=red"
+            } else if unbalanced.contains(id) {
+                ", color=orange, penwidth=2"
+            } else {
+                ""
+            };
+            writeln!(out, "    \"{}\" [label=\"{}\\nweight {}\\ntotal {}\"{}];", id.0, id.0, self.nodes[id].weight, weights[id], style).unwrap();
+            for child in self.children(*id) {
+                writeln!(out, "    \"{}\" -> \"{}\";", id.0, child.0).unwrap();
+            }
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    // indented like tree(1)
+    fn to_ascii(&self, weights: &HashMap<Id<'a>, u32>, culprit: Option<Id<'a>>) -> String {
+        let mut out = String::new();
+        let mut pending = vec![(self.root, String::new(), String::new())]; // (id, branch, indent for children)
+        while let Some((id, branch, indent)) = pending.pop() {
+            let marker = if Some(id) == culprit { " <-- wrong weight" } else { "" };
+            writeln!(out, "{}{} ({}, total {}){}", branch, id.0, self.nodes[&id].weight, weights[&id], marker).unwrap();
+            let children = self.children(id);
+            for (i, child) in children.iter().enumerate().rev() {
+                let last = i == children.len() - 1;
+                let branch = format!("{}{}", indent, if last { "└── " } else { "├── " });
+                let child_indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+                pending.push((*child, branch, child_indent));
+            }
+        }
+        out
+    }
 }
 
 fn parse(line: &str) -> Result<(Node, Vec<Id>), Box<Error>> {
@@ -90,11 +318,49 @@ fn parse(line: &str) -> Result<(Node, Vec<Id>), Box<Error>> {
 fn main() {
     let reader = BufReader::new(File::open("input").expect("Couldn't read input file."));
     let lines: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
-    let entries = lines.iter().map(|l| parse(l).expect("Couldn't parse line."));
-    let mut tree = Tree { nodes: HashMap::new(), edges: HashMap::new() };
-    for (program, links) in entries {
-        tree.edges.insert(program.id, links);
-        tree.nodes.insert(program.id, program);
+    let mut entries = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        match parse(line) {
+            Ok(entry) => { entries.push(entry); }
+            Err(e) => { println!("Line {}: {}", i + 1, e); return; }
+        }
+    }
+    let tree = match Tree::new(entries) {
+        Ok(tree) => tree,
+        Err(e) => { println!("Malformed tower: {}", e); return; }
+    };
+    let weights = subtree_weights(&tree);
+    let imbalances = find_imbalances(&tree, &weights);
+    let culprit = find_unbalanced(&tree, &imbalances, &weights);
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|a| a.as_str()) {
+        Some("--dot") => {
+            let unbalanced = imbalances.iter().map(|i| i.node).collect();
+            print!("{}", tree.to_dot(&weights, &unbalanced, culprit.ok().map(|(id, _)| id)));
+            return;
+        }
+        Some("--tree") => {
+            print!("{}", tree.to_ascii(&weights, culprit.ok().map(|(id, _)| id)));
+            return;
+        }
+        Some(arg) => panic!("Unrecognized argument: {}", arg),
+        None => {}
+    }
+
+    println!("Bottom program is: {}", tree.root.0);
+    for imbalance in &imbalances {
+        let children: Vec<String> = imbalance.children.iter().map(|&(id, w)| format!("{} ({})", id.0, w)).collect();
+        println!("Unbalanced: {} holds {}", imbalance.node.0, children.join(", "));
+        match imbalance.resolution {
+            Resolution::Outlier { child, target_weight } => println!("    {} should weigh {}", child.0, target_weight),
+            Resolution::Ambiguous(ref ids) => {
+                let names: Vec<&str> = ids.iter().map(|id| id.0).collect();
+                println!("    ambiguous between {}", names.join(", "));
+            }
+        }
+    }
+    match culprit {
+        Ok((id, weight)) => println!("Correct weight is: {:?}", (id, weight)),
+        Err(e) => println!("Couldn't find the wrong weight: {}", e),
     }
-    println!("Correct weight is: {:?}", find_unbalanced(&tree, Id("wiapj")));
 }
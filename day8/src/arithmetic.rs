@@ -0,0 +1,106 @@
+use num_bigint::BigInt;
+use num_traits::Zero;
+use std::fmt;
+use std::str::FromStr;
+use std::error::Error;
+
+// How register arithmetic behaves at the edges of the register type. Each operation returns
+// None if the result doesn't fit; only checked arithmetic ever does.
+pub trait Arithmetic {
+    type Value: Clone + Ord + fmt::Display + fmt::Debug;
+    fn value(n: i32) -> Self::Value;
+    fn add(a: &Self::Value, b: &Self::Value) -> Option<Self::Value>;
+    fn sub(a: &Self::Value, b: &Self::Value) -> Option<Self::Value>;
+    fn mul(a: &Self::Value, b: &Self::Value) -> Option<Self::Value>;
+    fn rem(a: &Self::Value, b: &Self::Value) -> Option<Self::Value>; // never called with b == 0
+    fn is_zero(a: &Self::Value) -> bool;
+}
+
+pub struct Checked;
+pub struct Wrapping;
+pub struct Saturating;
+pub struct Unbounded;
+
+impl Arithmetic for Checked {
+    type Value = i32;
+    fn value(n: i32) -> i32 { n }
+    fn add(a: &i32, b: &i32) -> Option<i32> { a.checked_add(*b) }
+    fn sub(a: &i32, b: &i32) -> Option<i32> { a.checked_sub(*b) }
+    fn mul(a: &i32, b: &i32) -> Option<i32> { a.checked_mul(*b) }
+    fn rem(a: &i32, b: &i32) -> Option<i32> { Some(a.wrapping_rem(*b)) } // MIN % -1 is just 0
+    fn is_zero(a: &i32) -> bool { *a == 0 }
+}
+
+impl Arithmetic for Wrapping {
+    type Value = i32;
+    fn value(n: i32) -> i32 { n }
+    fn add(a: &i32, b: &i32) -> Option<i32> { Some(a.wrapping_add(*b)) }
+    fn sub(a: &i32, b: &i32) -> Option<i32> { Some(a.wrapping_sub(*b)) }
+    fn mul(a: &i32, b: &i32) -> Option<i32> { Some(a.wrapping_mul(*b)) }
+    fn rem(a: &i32, b: &i32) -> Option<i32> { Some(a.wrapping_rem(*b)) }
+    fn is_zero(a: &i32) -> bool { *a == 0 }
+}
+
+impl Arithmetic for Saturating {
+    type Value = i32;
+    fn value(n: i32) -> i32 { n }
+    fn add(a: &i32, b: &i32) -> Option<i32> { Some(a.saturating_add(*b)) }
+    fn sub(a: &i32, b: &i32) -> Option<i32> { Some(a.saturating_sub(*b)) }
+    fn mul(a: &i32, b: &i32) -> Option<i32> { Some(a.saturating_mul(*b)) }
+    fn rem(a: &i32, b: &i32) -> Option<i32> { Some(a.wrapping_rem(*b)) }
+    fn is_zero(a: &i32) -> bool { *a == 0 }
+}
+
+impl Arithmetic for Unbounded {
+    type Value = BigInt;
+    fn value(n: i32) -> BigInt { BigInt::from(n) }
+    fn add(a: &BigInt, b: &BigInt) -> Option<BigInt> { Some(a + b) }
+    fn sub(a: &BigInt, b: &BigInt) -> Option<BigInt> { Some(a - b) }
+    fn mul(a: &BigInt, b: &BigInt) -> Option<BigInt> { Some(a * b) }
+    fn rem(a: &BigInt, b: &BigInt) -> Option<BigInt> { Some(a % b) }
+    fn is_zero(a: &BigInt) -> bool { a.is_zero() }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode { Checked, Wrapping, Saturating, Unbounded }
+
+impl FromStr for Mode {
+    type Err = Box<Error>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "checked" => Ok(Mode::Checked),
+            "wrapping" => Ok(Mode::Wrapping),
+            "saturating" => Ok(Mode::Saturating),
+            "bigint" => Ok(Mode::Unbounded),
+            _ => Err(From::from("Invalid arithmetic mode; expected checked, wrapping, saturating or bigint.")),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fault { Overflow, ModuloByZero }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArithmeticError {
+    pub line: usize,
+    pub register: String,
+    pub fault: Fault,
+}
+
+impl fmt::Display for ArithmeticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.fault {
+            Fault::Overflow => write!(f, "Line {}: register {} overflowed.", self.line, self.register),
+            Fault::ModuloByZero => write!(f, "Line {}: register {} taken modulo zero.", self.line, self.register),
+        }
+    }
+}
+
+impl Error for ArithmeticError {
+    fn description(&self) -> &str {
+        match self.fault {
+            Fault::Overflow => "register overflowed",
+            Fault::ModuloByZero => "modulo by zero",
+        }
+    }
+}
@@ -1,107 +1,223 @@
 #![feature(io)]
 #![feature(try_from)]
 
-#[macro_use]
-extern crate lazy_static;
-extern crate regex;
+extern crate num_bigint;
+extern crate num_traits;
+
+mod parser;
+mod arithmetic;
 
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
-use std::io::BufReader;
-use regex::Regex;
+use std::io::{BufReader, BufWriter};
 use std::error::Error;
 use std::collections::HashMap;
-use std::str::FromStr;
 use std::convert::TryFrom;
+use std::env;
+use std::cmp::Reverse;
+use std::fmt;
+use parser::{Cmp, Condition, Instruction, Op, Operand};
+use arithmetic::{Arithmetic, ArithmeticError, Checked, Fault, Mode, Saturating, Unbounded, Wrapping};
 
-lazy_static! {
-    static ref INSTRUCTION_RE: Regex = Regex::new(
-        r#"(?P<reg>\w+) (?P<op>\w+) (?P<val>\-?\d+) if (?P<target>\w+) (?P<cmp>.+) (?P<threshold>\-?\d+)"#
-    ).unwrap();
+fn value_of<A: Arithmetic>(registers: &HashMap<String, A::Value>, operand: Operand) -> A::Value {
+    match operand {
+        Operand::Literal(n) => A::value(n),
+        Operand::Register(reg) => registers.get(reg).cloned().unwrap_or_else(|| A::value(0)),
+    }
+}
+
+fn holds<A: Arithmetic>(registers: &HashMap<String, A::Value>, condition: &Condition) -> bool {
+    match *condition {
+        Condition::Always => true,
+        Condition::And(ref a, ref b) => holds::<A>(registers, a) && holds::<A>(registers, b),
+        Condition::Or(ref a, ref b) => holds::<A>(registers, a) || holds::<A>(registers, b),
+        Condition::Compare(lhs, cmp, rhs) => {
+            let (lhs, rhs) = (value_of::<A>(registers, lhs), value_of::<A>(registers, rhs));
+            match cmp {
+                Cmp::LessThan => lhs < rhs,
+                Cmp::LessEqual => lhs <= rhs,
+                Cmp::Equal => lhs == rhs,
+                Cmp::NotEqual => lhs != rhs,
+                Cmp::GreaterEqual => lhs >= rhs,
+                Cmp::GreaterThan => lhs > rhs,
+            }
+        }
+    }
+}
+
+// the register's new value, if the condition held
+fn execute<A: Arithmetic>(registers: &mut HashMap<String, A::Value>, instr: &Instruction) -> Result<Option<A::Value>, Fault> {
+    if !holds::<A>(registers, &instr.condition) {
+        return Ok(None);
+    }
+    let register_val = value_of::<A>(registers, Operand::Register(instr.reg));
+    let val = value_of::<A>(registers, instr.val);
+    let new_val = match instr.op {
+        Op::Inc => A::add(&register_val, &val),
+        Op::Dec => A::sub(&register_val, &val),
+        Op::Set => Some(val),
+        Op::Mul => A::mul(&register_val, &val),
+        Op::Mod if A::is_zero(&val) => { return Err(Fault::ModuloByZero); }
+        Op::Mod => A::rem(&register_val, &val),
+    }.ok_or(Fault::Overflow)?;
+    registers.insert(instr.reg.to_owned(), new_val.clone());
+    Ok(Some(new_val))
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Op { Inc, Dec }
+fn operand_register<'a>(operand: Operand<'a>) -> Option<&'a str> {
+    match operand {
+        Operand::Register(reg) => Some(reg),
+        Operand::Literal(_) => None,
+    }
+}
 
-impl FromStr for Op {
-    type Err = Box<Error>;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "inc" => Ok(Op::Inc),
-            "dec" => Ok(Op::Dec),
-            _ => Err(From::from("Invalid operation.")),
+fn condition_registers<'a>(condition: &Condition<'a>, names: &mut Vec<&'a str>) {
+    match *condition {
+        Condition::Always => {}
+        Condition::And(ref a, ref b) | Condition::Or(ref a, ref b) => {
+            condition_registers(a, names);
+            condition_registers(b, names);
         }
+        Condition::Compare(lhs, _, rhs) => names.extend(operand_register(lhs).into_iter().chain(operand_register(rhs))),
+    }
+}
+
+// every register the program mentions, including ones that are only ever read
+fn named_registers<'a>(program: &[Instruction<'a>]) -> Vec<&'a str> {
+    let mut names = Vec::new();
+    for instr in program {
+        names.push(instr.reg);
+        names.extend(operand_register(instr.val));
+        condition_registers(&instr.condition, &mut names);
     }
+    names
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Peak<V> {
+    pub value: V,
+    pub register: String,
+    pub instruction: usize, // index into the program, so one less than the line number
+}
+
+#[derive(Debug, Clone)]
+struct Execution<V> {
+    pub registers: HashMap<String, V>,
+    pub largest_final: Option<(String, V)>,
+    pub highest_ever: Option<Peak<V>>, // only counts values actually written, not the implicit zeroes
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Cmp { LessThan, LessEqual, Equal, NotEqual, GreaterThan, GreaterEqual }
-
-impl FromStr for Cmp {
-    type Err = Box<Error>;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "<" => Ok(Cmp::LessThan),
-            "<=" => Ok(Cmp::LessEqual),
-            "==" => Ok(Cmp::Equal),
-            "!=" => Ok(Cmp::NotEqual),
-            ">=" => Ok(Cmp::GreaterEqual),
-            ">" => Ok(Cmp::GreaterThan),
-            _ => Err(From::from("Invalid comparison operator.")),
+// Runs the whole program, writing one CSV row per instruction to trace if given. Every register
+// starts at 0, so one that is only read still counts towards the largest final value.
+fn run<A: Arithmetic>(program: &[Instruction], mut trace: Option<&mut Write>) -> Result<Execution<A::Value>, Box<Error>> {
+    let mut registers: HashMap<String, A::Value> = named_registers(program).into_iter().map(|reg| (reg.to_owned(), A::value(0))).collect();
+    let mut highest_ever: Option<Peak<A::Value>> = None;
+    if let Some(ref mut out) = trace {
+        writeln!(out, "line,register,executed,before,after")?;
+    }
+    for (i, instr) in program.iter().enumerate() {
+        let before = value_of::<A>(&registers, Operand::Register(instr.reg));
+        let result = execute::<A>(&mut registers, instr).map_err(|fault| {
+            ArithmeticError { line: i + 1, register: instr.reg.to_owned(), fault }
+        })?;
+        if let Some(ref mut out) = trace {
+            writeln!(out, "{},{},{},{},{}", i + 1, instr.reg, result.is_some(), before, result.as_ref().unwrap_or(&before))?;
+        }
+        if let Some(new_val) = result {
+            let is_peak = match highest_ever {
+                Some(ref peak) => new_val > peak.value,
+                None => true,
+            };
+            if is_peak {
+                highest_ever = Some(Peak { value: new_val, register: instr.reg.to_owned(), instruction: i });
+            }
         }
     }
+    let largest_final = registers.iter()
+        .max_by_key(|&(reg, val)| (val.clone(), Reverse(reg.clone()))) // smallest name breaks ties
+        .map(|(reg, val)| (reg.clone(), val.clone()));
+    Ok(Execution { registers, largest_final, highest_ever })
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Instruction<'a> {
-    pub reg: &'a str,
-    pub op: Op,
-    pub val: i32,
-    pub target: &'a str,
-    pub cmp: Cmp,
-    pub threshold: i32,
+fn show<V: Ord + fmt::Display>(registers: &HashMap<String, V>) -> String {
+    let mut entries: Vec<(&String, &V)> = registers.iter().collect();
+    entries.sort();
+    let parts: Vec<String> = entries.iter().map(|&(reg, val)| format!("{} = {}", reg, val)).collect();
+    parts.join(", ")
 }
 
-impl<'a> TryFrom<&'a str> for Instruction<'a> {    
-    type Error = Box<Error>;
-    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
-        let parts = INSTRUCTION_RE.captures(s).ok_or("Failed to parse line.")?;
-        let reg = parts.name("reg").unwrap().as_str();
-        let op = parts.name("op").unwrap().as_str().parse()?;
-        let val = parts.name("val").unwrap().as_str().parse()?;
-        let target = parts.name("target").unwrap().as_str();
-        let cmp = parts.name("cmp").unwrap().as_str().parse()?;
-        let threshold = parts.name("threshold").unwrap().as_str().parse()?;
-        Ok(Instruction { reg, op, val, target, cmp, threshold })
+fn repl<A: Arithmetic>() {
+    let stdin = io::stdin();
+    let mut registers = HashMap::new();
+    print!("> ");
+    io::stdout().flush().expect("Couldn't flush output.");
+    for (i, line) in stdin.lock().lines().enumerate() {
+        let line = line.expect("Couldn't read input line.");
+        if !line.trim().is_empty() {
+            let result = Instruction::try_from(line.as_str()).and_then(|instr| {
+                execute::<A>(&mut registers, &instr).map_err(|fault| {
+                    From::from(ArithmeticError { line: i + 1, register: instr.reg.to_owned(), fault })
+                })
+            });
+            match result {
+                Ok(Some(_)) => println!("{}", show(&registers)),
+                Ok(None) => println!("(condition false) {}", show(&registers)),
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+        print!("> ");
+        io::stdout().flush().expect("Couldn't flush output.");
+    }
+    println!();
+}
+
+fn report<A: Arithmetic>(program: &[Instruction], trace_path: Option<&String>) {
+    let result = match trace_path {
+        Some(path) => {
+            let mut out = BufWriter::new(File::create(path).expect("Couldn't create trace file."));
+            run::<A>(program, Some(&mut out))
+        }
+        None => run::<A>(program, None),
+    };
+    let execution = match result {
+        Ok(execution) => execution,
+        Err(e) => { println!("Couldn't execute program: {}", e); return; }
+    };
+
+    match execution.largest_final {
+        Some((reg, val)) => println!("Largest final value: {} in {}", val, reg),
+        None => println!("The program names no registers."),
+    }
+    if let Some(peak) = execution.highest_ever {
+        println!("Highest value ever held: {} in {}, set on line {}", peak.value, peak.register, peak.instruction + 1);
     }
+    println!("Final registers: {}", show(&execution.registers));
 }
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mode = match args.iter().position(|a| a == "--arithmetic") {
+        Some(i) => args.get(i + 1).expect("--arithmetic needs a mode.").parse().expect("Couldn't parse arithmetic mode."),
+        None => Mode::Checked,
+    };
+    if args.iter().any(|a| a == "--repl") {
+        return match mode {
+            Mode::Checked => repl::<Checked>(),
+            Mode::Wrapping => repl::<Wrapping>(),
+            Mode::Saturating => repl::<Saturating>(),
+            Mode::Unbounded => repl::<Unbounded>(),
+        };
+    }
+
     let reader = BufReader::new(File::open("input").expect("Couldn't read input file."));
     let lines: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
-    let entries = lines.iter().map(|l| Instruction::try_from(l.as_str()).expect("Couldn't parse line."));
-    let mut registers = HashMap::new();
-    let mut max_val = 0;
-    for entry in entries {
-        let register_val = *registers.get(entry.reg).unwrap_or(&0);
-        let target_val = *registers.get(entry.target).unwrap_or(&0);
-        let meets_condition = match entry.cmp {
-            Cmp::LessThan => target_val < entry.threshold,
-            Cmp::LessEqual => target_val <= entry.threshold,
-            Cmp::Equal => target_val == entry.threshold,
-            Cmp::NotEqual => target_val != entry.threshold,
-            Cmp::GreaterEqual => target_val >= entry.threshold,
-            Cmp::GreaterThan => target_val > entry.threshold,
-        };
-        if meets_condition {
-            let delta = match entry.op {
-                Op::Inc => entry.val,
-                Op::Dec => -entry.val,
-            };
-            let new_val = register_val + delta;
-            max_val = i32::max(new_val, max_val);
-            registers.insert(entry.reg, new_val);
-        }
+    let program: Vec<Instruction> = lines.iter().map(|l| Instruction::try_from(l.as_str()).expect("Couldn't parse line.")).collect();
+    let trace_path = args.iter().position(|a| a == "--trace").map(|i| args.get(i + 1).expect("--trace needs an output path."));
+    match mode {
+        Mode::Checked => report::<Checked>(&program, trace_path),
+        Mode::Wrapping => report::<Wrapping>(&program, trace_path),
+        Mode::Saturating => report::<Saturating>(&program, trace_path),
+        Mode::Unbounded => report::<Unbounded>(&program, trace_path),
     }
-    println!("Maximum value was: {:?}", max_val);
 }
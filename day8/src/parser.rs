@@ -0,0 +1,217 @@
+use std::error::Error;
+use std::str::FromStr;
+use std::convert::TryFrom;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Op { Inc, Dec, Set, Mul, Mod }
+
+impl FromStr for Op {
+    type Err = Box<Error>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inc" => Ok(Op::Inc),
+            "dec" => Ok(Op::Dec),
+            "set" => Ok(Op::Set),
+            "mul" => Ok(Op::Mul),
+            "mod" => Ok(Op::Mod),
+            _ => Err(From::from("Invalid operation.")),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Cmp { LessThan, LessEqual, Equal, NotEqual, GreaterThan, GreaterEqual }
+
+impl FromStr for Cmp {
+    type Err = Box<Error>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "<" => Ok(Cmp::LessThan),
+            "<=" => Ok(Cmp::LessEqual),
+            "==" => Ok(Cmp::Equal),
+            "!=" => Ok(Cmp::NotEqual),
+            ">=" => Ok(Cmp::GreaterEqual),
+            ">" => Ok(Cmp::GreaterThan),
+            _ => Err(From::from("Invalid comparison operator.")),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operand<'a> {
+    Register(&'a str),
+    Literal(i32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition<'a> {
+    Always,
+    Compare(Operand<'a>, Cmp, Operand<'a>),
+    And(Box<Condition<'a>>, Box<Condition<'a>>),
+    Or(Box<Condition<'a>>, Box<Condition<'a>>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction<'a> {
+    pub reg: &'a str,
+    pub op: Op,
+    pub val: Operand<'a>,
+    pub condition: Condition<'a>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Token<'a> {
+    Word(&'a str),
+    Integer(i32),
+    Cmp(Cmp),
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+// tokens paired with the column they start at, for error messages
+fn lex<'a>(s: &'a str) -> Result<Vec<(usize, Token<'a>)>, Box<Error>> {
+    let bytes = s.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let ch = bytes[i];
+        let starts_number = ch.is_ascii_digit() || (ch == b'-' && i + 1 < bytes.len() && bytes[i + 1].is_ascii_digit());
+        if ch.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if starts_number {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            let n = s[start..i].parse().map_err(|e| format!("Bad integer at column {}: {}", start + 1, e))?;
+            tokens.push((start, Token::Integer(n)));
+        } else if ch.is_ascii_alphanumeric() || ch == b'_' {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push((start, Token::Word(&s[start..i])));
+        } else {
+            let two = s.get(i..i + 2).unwrap_or("");
+            let (token, len) = match (two, ch) {
+                ("&&", _) => (Token::And, 2),
+                ("||", _) => (Token::Or, 2),
+                ("<=", _) | (">=", _) | ("==", _) | ("!=", _) => (Token::Cmp(two.parse()?), 2),
+                (_, b'<') => (Token::Cmp(Cmp::LessThan), 1),
+                (_, b'>') => (Token::Cmp(Cmp::GreaterThan), 1),
+                (_, b'(') => (Token::Open, 1),
+                (_, b')') => (Token::Close, 1),
+                _ => { return Err(From::from(format!("Unexpected character at column {}.", start + 1))); }
+            };
+            tokens.push((start, token));
+            i += len;
+        }
+    }
+    Ok(tokens)
+}
+
+// instruction := register op operand ["if" condition]
+// condition   := conjunction ("||" conjunction)*
+// conjunction := comparison ("&&" comparison)*
+// comparison  := operand cmp operand | "(" condition ")"
+struct Parser<'a> {
+    tokens: Vec<(usize, Token<'a>)>,
+    pos: usize,
+    len: usize, // of the source, for errors at the end of the line
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).map(|&(_, t)| t)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.len, |&(c, _)| c) + 1
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, Box<Error>> {
+        match self.peek() {
+            Some(t) => Err(From::from(format!("Expected {} at column {}, found {:?}.", expected, self.column(), t))),
+            None => Err(From::from(format!("Expected {} at column {}, found end of line.", expected, self.column()))),
+        }
+    }
+
+    fn word(&mut self, expected: &str) -> Result<&'a str, Box<Error>> {
+        match self.peek() {
+            Some(Token::Word(w)) => { self.pos += 1; Ok(w) }
+            _ => self.error(expected),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand<'a>, Box<Error>> {
+        match self.peek() {
+            Some(Token::Word(w)) if w != "if" => { self.pos += 1; Ok(Operand::Register(w)) }
+            Some(Token::Integer(n)) => { self.pos += 1; Ok(Operand::Literal(n)) }
+            _ => self.error("a register or integer"),
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Condition<'a>, Box<Error>> {
+        if self.peek() == Some(Token::Open) {
+            self.pos += 1;
+            let inner = self.condition()?;
+            if self.peek() != Some(Token::Close) {
+                return self.error("')'");
+            }
+            self.pos += 1;
+            return Ok(inner);
+        }
+        let lhs = self.operand()?;
+        let cmp = match self.peek() {
+            Some(Token::Cmp(cmp)) => { self.pos += 1; cmp }
+            _ => { return self.error("a comparison operator"); }
+        };
+        let rhs = self.operand()?;
+        Ok(Condition::Compare(lhs, cmp, rhs))
+    }
+
+    fn conjunction(&mut self) -> Result<Condition<'a>, Box<Error>> {
+        let mut result = self.comparison()?;
+        while self.peek() == Some(Token::And) {
+            self.pos += 1;
+            result = Condition::And(Box::new(result), Box::new(self.comparison()?));
+        }
+        Ok(result)
+    }
+
+    fn condition(&mut self) -> Result<Condition<'a>, Box<Error>> {
+        let mut result = self.conjunction()?;
+        while self.peek() == Some(Token::Or) {
+            self.pos += 1;
+            result = Condition::Or(Box::new(result), Box::new(self.conjunction()?));
+        }
+        Ok(result)
+    }
+
+    fn instruction(&mut self) -> Result<Instruction<'a>, Box<Error>> {
+        let reg = self.word("a register")?;
+        let column = self.column();
+        let op = self.word("an operation")?.parse().map_err(|e| format!("{} (column {})", e, column))?;
+        let val = self.operand()?;
+        let condition = match self.peek() {
+            None => Condition::Always,
+            Some(Token::Word("if")) => { self.pos += 1; self.condition()? }
+            _ => { return self.error("'if' or end of line"); }
+        };
+        if self.peek().is_some() {
+            return self.error("end of line");
+        }
+        Ok(Instruction { reg, op, val, condition })
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Instruction<'a> {
+    type Error = Box<Error>;
+    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
+        let mut parser = Parser { tokens: lex(s)?, pos: 0, len: s.len() };
+        parser.instruction()
+    }
+}
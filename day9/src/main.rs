@@ -1,37 +1,299 @@
-#![feature(io)]
+extern crate rayon;
+
+mod scanner;
 
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::BufWriter;
+use std::fmt;
+use std::cmp;
+use std::env;
+use rayon::prelude::*;
+use scanner::{ChunkSummary, Scanner, Totals};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Group {
+    pub offset: usize, // of the opening brace
+    pub children: Vec<Group>,
+    pub garbage: u32, // characters of garbage directly inside this group
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stream {
+    pub groups: Vec<Group>,
+    pub garbage: u32, // characters of garbage outside every group
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum StreamError {
+    UnterminatedGarbage { start: usize },
+    UnclosedGroup { start: usize },
+    UnexpectedClose { offset: usize },
+}
 
-fn consume_garbage<T>(input: &mut T) -> u32 where T: Iterator<Item=char> {
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StreamError::UnterminatedGarbage { start } => write!(f, "garbage starting at byte {} is never closed", start),
+            StreamError::UnclosedGroup { start } => write!(f, "group starting at byte {} is never closed", start),
+            StreamError::UnexpectedClose { offset } => write!(f, "byte {} closes a group that was never opened", offset),
+        }
+    }
+}
+
+// consumes everything up to and including the closing '>', given the offset of the opening '<'
+fn consume_garbage<T>(input: &mut T, start: usize) -> Result<u32, StreamError> where T: Iterator<Item=(usize, u8)> {
     let mut size = 0;
-    while let Some(ch) = input.next() {
+    while let Some((_, ch)) = input.next() {
         match ch {
-            '!' => { input.next(); }
-            '>' => { return size; },
+            b'!' => { input.next(); }
+            b'>' => { return Ok(size); },
             _ => { size += 1; }
         };
     }
-    panic!("Non-terminated garbage in input!");
+    Err(StreamError::UnterminatedGarbage { start })
 }
 
-fn main() {
-    let reader = BufReader::new(File::open("input").expect("Couldn't read input file."));
-    let mut input = reader.chars().map(|ch| ch.expect("Couldn't parse file."));
-    let mut score = 0;
-    while let Some(ch) = input.next() {
+fn parse(input: &[u8]) -> Result<Stream, StreamError> {
+    let mut stream = Stream { groups: Vec::new(), garbage: 0 };
+    let mut open: Vec<Group> = Vec::new(); // innermost last
+    let mut bytes = input.iter().cloned().enumerate();
+    while let Some((offset, ch)) = bytes.next() {
         match ch {
-            '<' => { score += consume_garbage(&mut input); },
-            '{' => {
-                // depth += 1;
+            b'<' => {
+                let size = consume_garbage(&mut bytes, offset)?;
+                match open.last_mut() {
+                    Some(group) => { group.garbage += size; }
+                    None => { stream.garbage += size; }
+                }
+            },
+            b'{' => {
+                open.push(Group { offset, children: Vec::new(), garbage: 0 });
             },
-            '}' => {
-                // score += depth;
-                // depth -= 1;
+            b'}' => {
+                let group = open.pop().ok_or(StreamError::UnexpectedClose { offset })?;
+                match open.last_mut() {
+                    Some(parent) => { parent.children.push(group); }
+                    None => { stream.groups.push(group); }
+                }
             },
             _ => {},
         };
     }
-    println!("Total score is: {}", score);
+    match open.last() {
+        Some(group) => Err(StreamError::UnclosedGroup { start: group.offset }),
+        None => Ok(stream),
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct GroupStats {
+    pub offset: usize,
+    pub depth: u32, // outermost groups have depth 1
+    pub children: usize,
+    pub descendants: usize,
+    pub score: u64, // of this group and everything inside it
+    pub garbage: u64, // likewise
+}
+
+// statistics for every group, in the order their opening braces appear
+fn group_stats(stream: &Stream) -> Vec<GroupStats> {
+    fn visit(group: &Group, depth: u32, out: &mut Vec<GroupStats>) -> GroupStats {
+        let index = out.len();
+        out.push(GroupStats { offset: group.offset, depth, children: group.children.len(), descendants: 0, score: depth as u64, garbage: group.garbage as u64 });
+        let mut totals = out[index];
+        for child in &group.children {
+            let child_stats = visit(child, depth + 1, out);
+            totals.descendants += 1 + child_stats.descendants;
+            totals.score += child_stats.score;
+            totals.garbage += child_stats.garbage;
+        }
+        out[index] = totals;
+        totals
+    }
+    let mut out = Vec::new();
+    for group in &stream.groups {
+        visit(group, 1, &mut out);
+    }
+    out
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Summary {
+    pub score: u64,
+    pub garbage: u64,
+    pub groups: usize,
+    pub max_depth: u32,
+}
+
+fn summarize(stream: &Stream, stats: &[GroupStats]) -> Summary {
+    Summary {
+        score: stats.iter().map(|s| s.depth as u64).sum(),
+        garbage: stream.garbage as u64 + stats.iter().filter(|s| s.depth == 1).map(|s| s.garbage).sum::<u64>(),
+        groups: stats.len(),
+        max_depth: stats.iter().fold(0, |acc, s| cmp::max(acc, s.depth)),
+    }
+}
+
+const CHUNK_SIZE: usize = 1 << 20;
+
+fn scan_chunked(path: &str) -> Result<Totals, StreamError> {
+    let mut file = File::open(path).expect("Couldn't read input file.");
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut scanner = Scanner::new();
+    loop {
+        let n = file.read(&mut buf).expect("Couldn't read input file.");
+        if n == 0 {
+            return scanner.finish();
+        }
+        scanner.feed(&buf[..n]);
+    }
+}
+
+// Each chunk is summarized for two possible entry states, so this does twice the work of
+// scan_chunked and only pays off with more than two cores to spread it over.
+fn scan_parallel(path: &str) -> Result<Totals, StreamError> {
+    // read a batch of chunks per thread at a time so memory stays bounded
+    let batch_size = CHUNK_SIZE * rayon::current_num_threads() * 4;
+    let mut file = File::open(path).expect("Couldn't read input file.");
+    let mut buf = Vec::with_capacity(batch_size);
+    let mut summary = ChunkSummary::identity();
+    loop {
+        buf.clear();
+        let n = (&mut file).take(batch_size as u64).read_to_end(&mut buf).expect("Couldn't read input file.");
+        if n == 0 {
+            break;
+        }
+        let batch = buf.par_chunks(CHUNK_SIZE).map(ChunkSummary::of).reduce(ChunkSummary::identity, |a, b| a.merge(&b));
+        summary = summary.merge(&batch);
+    }
+    match summary.totals() {
+        Some(totals) => Ok(totals),
+        None => scan_chunked(path), // only the sequential scan knows where it went wrong
+    }
+}
+
+// xorshift32, so --generate writes the same stream for the same length every time
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+// a well-formed stream of roughly the given length
+fn generate(rng: &mut Rng, length: usize, out: &mut Write) -> io::Result<()> {
+    let garbage_chars = b"abc{}<'\",!";
+    let mut depth = 0;
+    let mut written = 0;
+    let mut item = Vec::new();
+    while written < length || depth > 0 {
+        item.clear();
+        let choice = if written >= length { 0 } else { rng.next() % 8 };
+        match choice {
+            0..=2 if depth > 0 => { item.push(b'}'); depth -= 1; }
+            0..=4 => { item.push(b'{'); depth += 1; }
+            _ => {
+                item.push(b'<');
+                for _ in 0..rng.next() % 12 {
+                    let ch = garbage_chars[(rng.next() % garbage_chars.len() as u32) as usize];
+                    item.push(ch);
+                    if ch == b'!' {
+                        item.push(b">!a"[(rng.next() % 3) as usize]); // whatever follows is cancelled
+                    }
+                }
+                item.push(b'>');
+            }
+        }
+        if depth > 0 && item[0] != b'}' {
+            item.push(b',');
+        }
+        out.write_all(&item)?;
+        written += item.len();
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let path = args.get(1).map_or("input", |p| p.as_str());
+    let totals = match args.first().map(|a| a.as_str()) {
+        Some("--generate") => {
+            let length = args.get(1).and_then(|n| n.parse().ok()).expect("--generate needs a length.");
+            let stdout = io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            generate(&mut Rng(2463534242), length, &mut out).expect("Couldn't write stream.");
+            return;
+        }
+        Some("--chunked") => Some(scan_chunked(path)),
+        Some("--parallel") => Some(scan_parallel(path)),
+        _ => None,
+    };
+    if let Some(totals) = totals {
+        match totals {
+            Ok(totals) => println!("Total score is: {}\nGarbage characters: {}", totals.score, totals.garbage),
+            Err(e) => println!("Malformed stream: {}", e),
+        }
+        return;
+    }
+
+    let mut input = Vec::new();
+    File::open("input").expect("Couldn't read input file.").read_to_end(&mut input).expect("Couldn't read input file.");
+    let stream = match parse(&input) {
+        Ok(stream) => stream,
+        Err(e) => { println!("Malformed stream: {}", e); return; }
+    };
+    let stats = group_stats(&stream);
+    if env::args().any(|a| a == "--groups") {
+        for s in &stats {
+            println!("{:>6}: depth {}, {} children, {} descendants, score {}, garbage {}", s.offset, s.depth, s.children, s.descendants, s.score, s.garbage);
+        }
+    }
+    let summary = summarize(&stream, &stats);
+    println!("Total score is: {}", summary.score);
+    println!("Garbage characters: {}", summary.garbage);
+    println!("Groups: {}, deepest nesting: {}", summary.groups, summary.max_depth);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // random streams, some of them malformed, split into random chunks
+    #[test]
+    fn scans_match_parser() {
+        let mut rng = Rng(2463534242);
+        for trial in 0..1000 {
+            let mut stream = Vec::new();
+            let length = (rng.next() % 5000) as usize;
+            generate(&mut rng, length, &mut stream).unwrap();
+            if trial % 4 == 0 && !stream.is_empty() {
+                // make some of them malformed
+                let at = (rng.next() as usize) % stream.len();
+                stream[at] = b"{}<>!"[(rng.next() % 5) as usize];
+            }
+
+            let expected = parse(&stream).map(|s| {
+                let summary = summarize(&s, &group_stats(&s));
+                Totals { score: summary.score, garbage: summary.garbage }
+            });
+
+            let mut scanner = Scanner::new();
+            let mut summary = ChunkSummary::identity();
+            let mut rest = &stream[..];
+            while !rest.is_empty() {
+                let (chunk, tail) = rest.split_at(1 + rng.next() as usize % rest.len().min(300));
+                scanner.feed(chunk);
+                summary = summary.merge(&ChunkSummary::of(chunk));
+                rest = tail;
+            }
+            assert_eq!(expected, scanner.finish(), "Scanner disagrees with the parser on trial {}!", trial);
+            assert_eq!(expected.ok(), summary.totals(), "Merged summaries disagree with the parser on trial {}!", trial);
+        }
+    }
 }
@@ -0,0 +1,176 @@
+use StreamError;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Lexical {
+    Normal,
+    Garbage,
+    Cancelled, // in garbage, right after a '!'
+}
+
+fn index(state: Lexical) -> usize {
+    match state {
+        Lexical::Normal => 0,
+        Lexical::Garbage => 1,
+        Lexical::Cancelled => 2,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Totals {
+    pub score: u64,
+    pub garbage: u64,
+}
+
+// A resumable scanner which can be fed the stream in arbitrary pieces.
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    offset: usize,
+    state: Lexical,
+    open: Vec<usize>, // offsets of the groups we're inside, innermost last
+    garbage_start: usize,
+    totals: Totals,
+    error: Option<StreamError>,
+}
+
+impl Scanner {
+    pub fn new() -> Self {
+        Self { offset: 0, state: Lexical::Normal, open: Vec::new(), garbage_start: 0, totals: Totals { score: 0, garbage: 0 }, error: None }
+    }
+
+    // input after the first error is ignored
+    pub fn feed(&mut self, chunk: &[u8]) {
+        if self.error.is_some() {
+            return;
+        }
+        for (i, ch) in chunk.iter().enumerate() {
+            let offset = self.offset + i;
+            self.state = match (self.state, *ch) {
+                (Lexical::Normal, b'<') => { self.garbage_start = offset; Lexical::Garbage }
+                (Lexical::Normal, b'{') => {
+                    self.open.push(offset);
+                    self.totals.score += self.open.len() as u64;
+                    Lexical::Normal
+                }
+                (Lexical::Normal, b'}') => {
+                    if self.open.pop().is_none() {
+                        self.error = Some(StreamError::UnexpectedClose { offset });
+                        return;
+                    }
+                    Lexical::Normal
+                }
+                (Lexical::Normal, _) => Lexical::Normal,
+                (Lexical::Garbage, b'!') => Lexical::Cancelled,
+                (Lexical::Garbage, b'>') => Lexical::Normal,
+                (Lexical::Garbage, _) => { self.totals.garbage += 1; Lexical::Garbage }
+                (Lexical::Cancelled, _) => Lexical::Garbage,
+            };
+        }
+        self.offset += chunk.len();
+    }
+
+    pub fn finish(self) -> Result<Totals, StreamError> {
+        if let Some(e) = self.error {
+            Err(e)
+        } else if self.state != Lexical::Normal {
+            Err(StreamError::UnterminatedGarbage { start: self.garbage_start })
+        } else if let Some(start) = self.open.last() {
+            Err(StreamError::UnclosedGroup { start: *start })
+        } else {
+            Ok(self.totals)
+        }
+    }
+}
+
+// What a piece of the stream does when entered in a given lexical state, measured
+// relative to whatever depth it's entered at.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Partial {
+    pub end: Lexical,
+    pub garbage: u64,
+    pub opens: u64,
+    pub score: i64, // entered at depth d, the piece scores score + opens * d
+    pub net: i64, // change in depth
+    pub lowest: i64, // lowest depth reached; below zero means a stray '}' at depth 0
+}
+
+impl Partial {
+    fn scan(start: Lexical, chunk: &[u8]) -> Partial {
+        let mut p = Partial { end: start, garbage: 0, opens: 0, score: 0, net: 0, lowest: 0 };
+        for ch in chunk {
+            p.end = match (p.end, *ch) {
+                (Lexical::Normal, b'<') => Lexical::Garbage,
+                (Lexical::Normal, b'{') => {
+                    p.net += 1;
+                    p.opens += 1;
+                    p.score += p.net;
+                    Lexical::Normal
+                }
+                (Lexical::Normal, b'}') => {
+                    p.net -= 1;
+                    p.lowest = p.lowest.min(p.net);
+                    Lexical::Normal
+                }
+                (Lexical::Normal, _) => Lexical::Normal,
+                (Lexical::Garbage, b'!') => Lexical::Cancelled,
+                (Lexical::Garbage, b'>') => Lexical::Normal,
+                (Lexical::Garbage, _) => { p.garbage += 1; Lexical::Garbage }
+                (Lexical::Cancelled, _) => Lexical::Garbage,
+            };
+        }
+        p
+    }
+}
+
+// A summary of a piece of the stream for every lexical state it might be entered in.
+// Pieces can be summarized independently and then merged in order, so a stream can be
+// split anywhere and scanned in parallel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ChunkSummary([Partial; 3]);
+
+impl ChunkSummary {
+    pub fn identity() -> Self {
+        let partial = |end| Partial { end, garbage: 0, opens: 0, score: 0, net: 0, lowest: 0 };
+        ChunkSummary([partial(Lexical::Normal), partial(Lexical::Garbage), partial(Lexical::Cancelled)])
+    }
+
+    // Two passes rather than three: entered just after a '!', a piece skips its first byte and
+    // reads the rest as garbage, which is what the Garbage pass does too unless that first byte
+    // ends or cancels something.
+    pub fn of(chunk: &[u8]) -> Self {
+        let normal = Partial::scan(Lexical::Normal, chunk);
+        let garbage = Partial::scan(Lexical::Garbage, chunk);
+        let cancelled = match chunk.first() {
+            None => Partial::scan(Lexical::Cancelled, chunk),
+            Some(&b'!') | Some(&b'>') => Partial::scan(Lexical::Garbage, &chunk[1..]),
+            Some(_) => Partial { garbage: garbage.garbage - 1, ..garbage },
+        };
+        ChunkSummary([normal, garbage, cancelled])
+    }
+
+    // this piece followed by the next one
+    pub fn merge(&self, next: &ChunkSummary) -> ChunkSummary {
+        let mut merged = *self;
+        for (m, a) in merged.0.iter_mut().zip(self.0.iter()) {
+            let b = next.0[index(a.end)];
+            *m = Partial {
+                end: b.end,
+                garbage: a.garbage + b.garbage,
+                opens: a.opens + b.opens,
+                score: a.score + b.score + b.opens as i64 * a.net,
+                net: a.net + b.net,
+                lowest: a.lowest.min(a.net + b.lowest),
+            };
+        }
+        merged
+    }
+
+    // the totals for a whole stream, or None if it's malformed (rescan it with a Scanner to find out where)
+    pub fn totals(&self) -> Option<Totals> {
+        let p = self.0[index(Lexical::Normal)];
+        if p.end == Lexical::Normal && p.net == 0 && p.lowest >= 0 {
+            Some(Totals { score: p.score as u64, garbage: p.garbage })
+        } else {
+            None
+        }
+    }
+}